@@ -0,0 +1,109 @@
+//! Measures how long a large machine takes to compile, and how big it ends up, by generating a
+//! crate defining one and timing `cargo build` on it.
+//!
+//! Run with `cargo bench --bench compile_time`. The machine has `FSM_BENCH_STATES` states and as
+//! many events (200 by default), and each state has transitions for `FSM_BENCH_TRANSITIONS` of the
+//! events (10 by default).
+
+use std::{env, fmt::Write, fs, path::Path, process::Command, time::Duration, time::Instant};
+
+fn env_or(var: &str, default: usize) -> usize {
+    env::var(var)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// The source of a crate defining the machine
+fn machine_source(states: usize, transitions: usize) -> String {
+    let mut src = String::from(
+        "use rustfsm::{fsm, TransitionResult};\n\
+         use std::convert::Infallible;\n\n\
+         fsm! {\n    Machine, Command, Infallible\n\n",
+    );
+    let mut lines = vec![];
+    for s in 0..states {
+        for t in 0..transitions {
+            let event = (s + t) % states;
+            let to = (s + t + 1) % states;
+            if t == 0 {
+                lines.push(format!("    S{} --(E{}(u32), on_e)--> S{}", s, event, to));
+            } else {
+                lines.push(format!("    S{} --(E{}(u32))--> S{}", s, event, to));
+            }
+        }
+    }
+    src.push_str(&lines.join(";\n"));
+    src.push_str("\n}\n\npub enum Command {\n    Count(u32),\n}\n");
+    for s in 0..states {
+        write!(
+            src,
+            "\n#[derive(Default)]\npub struct S{s} {{}}\n\
+             impl S{s} {{\n    \
+                 fn on_e(&self, n: u32) -> MachineTransition {{\n        \
+                     TransitionResult::ok(vec![Command::Count(n)], S{to} {{}})\n    \
+                 }}\n\
+             }}\n",
+            s = s,
+            to = (s + 1) % states
+        )
+        .unwrap();
+    }
+    src
+}
+
+fn cargo_build(dir: &Path, target_dir: &Path) -> Duration {
+    let started = Instant::now();
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--release", "--quiet"])
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .status()
+        .expect("Couldn't run cargo");
+    assert!(status.success(), "Building the generated crate failed");
+    started.elapsed()
+}
+
+fn main() {
+    let states = env_or("FSM_BENCH_STATES", 200);
+    let transitions = env_or("FSM_BENCH_TRANSITIONS", 10).min(states);
+    println!(
+        "Machine with {} states, {} events and {} transitions",
+        states,
+        states,
+        states * transitions
+    );
+
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fsm_compile_time");
+    let crate_dir = root.join("machine");
+    fs::create_dir_all(crate_dir.join("src")).unwrap();
+    fs::write(
+        crate_dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"machine\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n\
+             [dependencies]\nrustfsm = {{ path = {:?} }}\n\n[workspace]\n",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+    )
+    .unwrap();
+    let target_dir = root.join("target");
+
+    // Build the dependencies first, so only the machine itself gets timed
+    fs::write(crate_dir.join("src/lib.rs"), "").unwrap();
+    cargo_build(&crate_dir, &target_dir);
+
+    fs::write(
+        crate_dir.join("src/lib.rs"),
+        machine_source(states, transitions),
+    )
+    .unwrap();
+    let elapsed = cargo_build(&crate_dir, &target_dir);
+    let size = fs::metadata(target_dir.join("release/libmachine.rlib"))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    println!(
+        "Compiled in {:.2}s, rlib is {} KiB",
+        elapsed.as_secs_f64(),
+        size / 1024
+    );
+}
@@ -0,0 +1,108 @@
+//! Measures how fast a machine handles events.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use std::{hint::black_box, time::Instant};
+
+/// Defines the states of a machine, all of them with the same handlers
+macro_rules! states {
+    ($($state:ident),*) => {
+        $(
+            #[derive(Default)]
+            pub struct $state {}
+            impl $state {
+                fn on_e0(&self) -> MachineTransition {
+                    TransitionResult::default::<S1>()
+                }
+                fn on_e15(&self, n: u32) -> MachineTransition {
+                    TransitionResult::ok(vec![Command::Count(n)], S0 {})
+                }
+            }
+        )*
+    };
+}
+
+/// A machine of 16 states which all accept 16 events
+mod machine {
+    use rustfsm::{fsm, TransitionResult};
+    use std::convert::Infallible;
+
+    fsm! {
+        Machine, Command, Infallible
+
+        * --(E0, on_e0)--> S1;
+        * --(E1)--> S2;
+        * --(E2)--> S3;
+        * --(E3)--> S4;
+        * --(E4)--> S5;
+        * --(E5)--> S6;
+        * --(E6)--> S7;
+        * --(E7)--> S8;
+        * --(E8)--> S9;
+        * --(E9)--> S10;
+        * --(E10)--> S11;
+        * --(E11)--> S12;
+        * --(E12)--> S13;
+        * --(E13)--> S14;
+        * --(E14)--> S15;
+        * --(E15(u32), on_e15)--> S0
+    }
+
+    pub enum Command {
+        Count(u32),
+    }
+
+    states!(S0, S1, S2, S3, S4, S5, S6, S7, S8, S9, S10, S11, S12, S13, S14, S15);
+
+    pub fn event(i: u32) -> MachineEvents {
+        match i % 16 {
+            0 => MachineEvents::E0,
+            1 => MachineEvents::E1,
+            2 => MachineEvents::E2,
+            3 => MachineEvents::E3,
+            4 => MachineEvents::E4,
+            5 => MachineEvents::E5,
+            6 => MachineEvents::E6,
+            7 => MachineEvents::E7,
+            8 => MachineEvents::E8,
+            9 => MachineEvents::E9,
+            10 => MachineEvents::E10,
+            11 => MachineEvents::E11,
+            12 => MachineEvents::E12,
+            13 => MachineEvents::E13,
+            14 => MachineEvents::E14,
+            _ => MachineEvents::E15(i),
+        }
+    }
+
+    /// Handles `n` events, returning the total of the counts in the commands produced
+    pub fn run(n: u32) -> u64 {
+        use rustfsm::StateMachine;
+
+        let mut machine = Machine::S0(S0 {});
+        let mut total = 0;
+        for i in 0..n {
+            // Skip around the events so the branch predictor can't learn the pattern
+            let (new_state, cmds) = machine.on_event(event(i.wrapping_mul(7))).unwrap();
+            total += cmds
+                .iter()
+                .map(|Command::Count(n)| u64::from(*n))
+                .sum::<u64>();
+            machine = new_state;
+        }
+        total
+    }
+}
+
+const EVENTS: u32 = 10_000_000;
+
+fn main() {
+    // Warm up first
+    black_box(machine::run(black_box(EVENTS / 10)));
+    let started = Instant::now();
+    black_box(machine::run(black_box(EVENTS)));
+    println!(
+        "{:.2} ns/event",
+        started.elapsed().as_nanos() as f64 / EVENTS as f64
+    );
+}
@@ -0,0 +1,85 @@
+//! A card reader machine for firmware with no allocator, whose transitions' commands go in a
+//! fixed-capacity `CommandBuffer` rather than a `Vec`
+
+#![no_std]
+
+use core::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+use rustfsm::{fsm, CommandBuffer, TransitionResult};
+
+fsm! {
+    // No transition produces more than two commands
+    #[fsm(commands = CommandBuffer<Commands, 2>)]
+    CardReader, Commands, CardError
+
+    Locked --(CardReadable(CardData), on_card_readable) --> ReadingCard;
+    ReadingCard --(CardDataChunk(CardData), on_chunk);
+    ReadingCard --(CardAccepted, on_card_accepted) --> DoorOpen;
+    ReadingCard --(CardRejected, on_card_rejected) --> Locked;
+    DoorOpen --(DoorClosed, on_door_closed) --> Locked
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Commands {
+    StartBlinkingLight,
+    StopBlinkingLight,
+    ProcessData(CardData),
+}
+
+pub type CardData = [u8; 16];
+
+/// How many chunks of card data a reader will take before giving up on the card
+pub const MAX_CHUNKS: usize = 4;
+
+#[derive(Debug)]
+pub struct CardError;
+
+impl Display for CardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("The card sent too much data")
+    }
+}
+
+impl Error for CardError {}
+
+#[derive(Default)]
+pub struct Locked {}
+impl Locked {
+    fn on_card_readable(&self, data: CardData) -> CardReaderTransition {
+        TransitionResult::ok(
+            [Commands::ProcessData(data), Commands::StartBlinkingLight],
+            ReadingCard { chunks: 0 },
+        )
+    }
+}
+
+pub struct ReadingCard {
+    chunks: usize,
+}
+impl ReadingCard {
+    fn on_chunk(&mut self, data: CardData) -> Result<CommandBuffer<Commands, 1>, CardError> {
+        if self.chunks == MAX_CHUNKS {
+            return Err(CardError);
+        }
+        self.chunks += 1;
+        let mut commands = CommandBuffer::new();
+        commands.extend([Commands::ProcessData(data)]);
+        Ok(commands)
+    }
+    fn on_card_accepted(&self) -> CardReaderTransition {
+        TransitionResult::ok([Commands::StopBlinkingLight], DoorOpen {})
+    }
+    fn on_card_rejected(&self) -> CardReaderTransition {
+        TransitionResult::ok([Commands::StopBlinkingLight], Locked {})
+    }
+}
+
+#[derive(Default)]
+pub struct DoorOpen {}
+impl DoorOpen {
+    fn on_door_closed(&self) -> CardReaderTransition {
+        TransitionResult::ok([], Locked {})
+    }
+}
@@ -0,0 +1,100 @@
+//! Runs the no-alloc card reader, checking its commands come out of the `CommandBuffer`s intact
+
+use no_alloc_example::*;
+use rustfsm::{CommandBuffer, StateMachine, TransitionError, TransitionResult};
+
+const CARD: CardData = [7; 16];
+
+#[test]
+fn commands_come_back_in_order() {
+    let (reader, commands) = CardReader::Locked(Locked {})
+        .on_event(CardReaderEvents::CardReadable(CARD))
+        .unwrap();
+    assert_eq!(reader.name(), "ReadingCard");
+    assert_eq!(
+        commands.into_iter().collect::<Vec<_>>(),
+        [Commands::ProcessData(CARD), Commands::StartBlinkingLight]
+    );
+
+    let (reader, commands) = reader
+        .on_event(CardReaderEvents::CardDataChunk(CARD))
+        .unwrap();
+    assert_eq!(reader.name(), "ReadingCard");
+    assert_eq!(
+        commands.into_iter().collect::<Vec<_>>(),
+        [Commands::ProcessData(CARD)]
+    );
+
+    let (reader, commands) = reader.on_event(CardReaderEvents::CardAccepted).unwrap();
+    assert_eq!(reader.name(), "DoorOpen");
+    assert_eq!(
+        commands.into_iter().collect::<Vec<_>>(),
+        [Commands::StopBlinkingLight]
+    );
+
+    let (reader, commands) = reader.on_event(CardReaderEvents::DoorClosed).unwrap();
+    assert_eq!(reader.name(), "Locked");
+    assert!(commands.into_iter().next().is_none());
+}
+
+#[test]
+fn commands_can_go_into_a_buffer_sink() {
+    let mut sink = CommandBuffer::<Commands, 4>::new();
+    let reader = CardReader::Locked(Locked {})
+        .on_event_into(CardReaderEvents::CardReadable(CARD), &mut sink)
+        .unwrap();
+    let reader = reader
+        .on_event_into(CardReaderEvents::CardRejected, &mut sink)
+        .unwrap();
+    assert_eq!(reader.name(), "Locked");
+    assert_eq!(
+        sink.into_iter().collect::<Vec<_>>(),
+        [
+            Commands::ProcessData(CARD),
+            Commands::StartBlinkingLight,
+            Commands::StopBlinkingLight
+        ]
+    );
+}
+
+#[test]
+fn handler_errors_are_reported() {
+    let (mut reader, _) = CardReader::Locked(Locked {})
+        .on_event(CardReaderEvents::CardReadable(CARD))
+        .unwrap();
+    for _ in 0..MAX_CHUNKS {
+        reader = reader
+            .on_event(CardReaderEvents::CardDataChunk(CARD))
+            .unwrap()
+            .0;
+    }
+    assert!(matches!(
+        reader.on_event(CardReaderEvents::CardDataChunk(CARD)),
+        TransitionResult::Err(CardError)
+    ));
+
+    let mut sink = CommandBuffer::<Commands, 4>::new();
+    assert!(matches!(
+        CardReader::DoorOpen(DoorOpen {}).on_event_into(CardReaderEvents::CardAccepted, &mut sink),
+        Err(TransitionError::InvalidTransition(_))
+    ));
+    assert!(sink.is_empty());
+}
+
+#[test]
+fn full_buffers_hand_back_what_does_not_fit() {
+    let mut sink = CommandBuffer::<Commands, 2>::new();
+    assert_eq!(
+        sink.try_extend([
+            Commands::StartBlinkingLight,
+            Commands::StopBlinkingLight,
+            Commands::ProcessData(CARD),
+        ]),
+        Err(Commands::ProcessData(CARD))
+    );
+    assert_eq!(sink.len(), 2);
+    assert_eq!(
+        sink.push(Commands::StartBlinkingLight),
+        Err(Commands::StartBlinkingLight)
+    );
+}
@@ -0,0 +1,77 @@
+//! A card reader machine for firmware, where there is an allocator but no `std`
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use core::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+use rustfsm::{fsm, TransitionResult};
+
+fsm! {
+    CardReader, Commands, CardError
+
+    Locked --(CardReadable(CardData), on_card_readable) --> ReadingCard;
+    ReadingCard --(CardDataChunk(CardData), on_chunk);
+    ReadingCard --(CardAccepted, on_card_accepted) --> DoorOpen;
+    ReadingCard --(CardRejected, on_card_rejected) --> Locked;
+    DoorOpen --(DoorClosed, on_door_closed) --> Locked
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Commands {
+    StartBlinkingLight,
+    StopBlinkingLight,
+    ProcessData(CardData),
+}
+
+type CardData = [u8; 16];
+
+#[derive(Debug)]
+pub struct CardError;
+
+impl Display for CardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("The card could not be read")
+    }
+}
+
+impl Error for CardError {}
+
+#[derive(Default)]
+pub struct Locked {}
+impl Locked {
+    fn on_card_readable(&self, data: CardData) -> CardReaderTransition {
+        TransitionResult::ok(
+            vec![Commands::ProcessData(data), Commands::StartBlinkingLight],
+            ReadingCard { chunks: 0 },
+        )
+    }
+}
+
+pub struct ReadingCard {
+    chunks: usize,
+}
+impl ReadingCard {
+    fn on_chunk(&mut self, data: CardData) -> Result<Vec<Commands>, CardError> {
+        self.chunks += 1;
+        Ok(vec![Commands::ProcessData(data)])
+    }
+    fn on_card_accepted(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![Commands::StopBlinkingLight], DoorOpen {})
+    }
+    fn on_card_rejected(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![Commands::StopBlinkingLight], Locked {})
+    }
+}
+
+#[derive(Default)]
+pub struct DoorOpen {}
+impl DoorOpen {
+    fn on_door_closed(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![], Locked {})
+    }
+}
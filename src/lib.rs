@@ -1,2 +1,120 @@
+//! Finite state machines, defined with the [fsm] macro. This crate re-exports everything needed to
+//! define and work with machines, and is what the code generated by [fsm] refers to, so it's the
+//! only dependency a crate defining machines needs.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Lets the generated code's `::rustfsm` paths resolve inside this crate too
+extern crate self as rustfsm;
+
 pub use state_machine_procmacro::fsm;
-pub use state_machine_trait::StateMachine;
+pub use state_machine_trait::*;
+
+/// Runs the dispatch of a generated `on_event`. With the `tracing` feature, that happens inside a
+/// `transition` span, recording how it went. Without it, this is just the dispatch. Living here
+/// rather than in the macro means the generated code always matches this crate's features.
+///
+/// With a `sink`, commands emitted into it are counted too, by wrapping it in a [CountingSink].
+#[doc(hidden)]
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! __fsm_transition {
+    (
+        machine: $machine:expr,
+        from: $from:expr,
+        event: $event:expr,
+        to: |$new_state:ident| $to:expr,
+        dispatch: $dispatch:expr $(,)?
+    ) => {{
+        let from = $from;
+        let event_name = $event;
+        let span = $crate::__fsm_transition!(@span $machine, from, event_name);
+        let _entered = span.enter();
+        // The dispatch returns early for invalid transitions, so run it in a closure
+        let result = (move || $dispatch)();
+        $crate::__fsm_transition!(
+            @record span, result, 0, $machine, from, event_name, |$new_state| $to
+        );
+        result
+    }};
+    (
+        machine: $machine:expr,
+        from: $from:expr,
+        event: $event:expr,
+        to: |$new_state:ident| $to:expr,
+        sink: $sink:ident: $command:ty,
+        dispatch: $dispatch:expr $(,)?
+    ) => {{
+        let from = $from;
+        let event_name = $event;
+        let span = $crate::__fsm_transition!(@span $machine, from, event_name);
+        let _entered = span.enter();
+        let mut counted = $crate::CountingSink::new($sink);
+        let $sink: &mut dyn $crate::CommandSink<$command> = &mut counted;
+        let result = (move || $dispatch)();
+        $crate::__fsm_transition!(
+            @record span, result, counted.count(), $machine, from, event_name, |$new_state| $to
+        );
+        result
+    }};
+    (@span $machine:expr, $from:ident, $event:ident) => {
+        $crate::tracing::info_span!(
+            "transition",
+            machine = $machine,
+            from = $from,
+            event = $event,
+            to = $crate::tracing::field::Empty,
+            outcome = $crate::tracing::field::Empty,
+            commands = $crate::tracing::field::Empty,
+        )
+    };
+    (
+        @record $span:ident, $result:ident, $emitted:expr, $machine:expr, $from:ident,
+        $event:ident, |$new_state:ident| $to:expr
+    ) => {
+        match &$result {
+            $crate::TransitionResult::Ok {
+                commands,
+                new_state: $new_state,
+            } => {
+                $span.record("to", &$to);
+                $span.record("outcome", &"ok");
+                $span.record(
+                    "commands",
+                    &($emitted + $crate::CommandCollection::len(commands)),
+                );
+            }
+            $crate::TransitionResult::InvalidTransition(_) => {
+                $span.record("outcome", &"invalid");
+                $crate::tracing::warn!(
+                    machine = $machine,
+                    from = $from,
+                    event = $event,
+                    "Invalid transition"
+                );
+            }
+            $crate::TransitionResult::Err(_) => {
+                $span.record("outcome", &"error");
+            }
+        }
+    };
+}
+
+/// Runs the dispatch of a generated `on_event`. With the `tracing` feature, that happens inside a
+/// `transition` span, recording how it went. Without it, this is just the dispatch. Living here
+/// rather than in the macro means the generated code always matches this crate's features.
+#[doc(hidden)]
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! __fsm_transition {
+    (
+        machine: $machine:expr,
+        from: $from:expr,
+        event: $event:expr,
+        to: |$new_state:ident| $to:expr,
+        $(sink: $sink:ident: $command:ty,)?
+        dispatch: $dispatch:expr $(,)?
+    ) => {
+        $dispatch
+    };
+}
@@ -2,14 +2,14 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::{quote, quote_spanned};
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
 use syn::{
     parenthesized,
     parse::{Parse, ParseStream, Result},
     parse_macro_input,
     punctuated::Punctuated,
     spanned::Spanned,
-    Error, Fields, Ident, Token, Variant,
+    Attribute, Error, Fields, Ident, Lifetime, Lit, Meta, MetaNameValue, Token, Type, Variant,
 };
 
 /// Parses a DSL for defining finite state machines, and produces code implementing the
@@ -17,16 +17,17 @@ use syn::{
 ///
 /// An example state machine definition of a card reader for unlocking a door:
 /// ```
-/// use state_machine_procmacro::fsm;
+/// use rustfsm::{fsm, StateMachine, TransitionResult};
 /// use std::convert::Infallible;
-/// use state_machine_trait::{StateMachine, TransitionResult};
 ///
 /// fsm! {
 ///     CardReader, Commands, Infallible
 ///
+///     /// Someone's presented a card for reading
 ///     Locked --(CardReadable(CardData), on_card_readable) --> ReadingCard;
 ///     ReadingCard --(CardAccepted, on_card_accepted) --> DoorOpen;
 ///     ReadingCard --(CardRejected, on_card_rejected) --> Locked;
+///     /// Door latch connected
 ///     DoorOpen --(DoorClosed, on_door_closed) --> Locked;
 /// }
 ///
@@ -109,11 +110,135 @@ use syn::{
 /// allowed are unit and one-item tuple variants. For unit variants, the function takes no
 /// parameters. For the tuple variants, the function takes the variant data as its parameter. In
 /// either case the function is expected to return a `TransitionResult` to the appropriate state.
+/// It may instead return a `Result` of one, whose error is the machine's error type, so that it can
+/// use `?` on fallible calls:
+/// ```ignore
+/// fn on_card_readable(&self, data: CardData) -> Result<CardReaderTransition, CardError> {
+///     let data = validate(data)?;
+///     Ok(TransitionResult::ok(vec![Commands::ProcessData(data)], ReadingCard {}))
+/// }
+/// ```
 ///
 /// The first transition can be interpreted as "If the machine is in the locked state, when a
 /// `CardReadable` event is seen, call `on_card_readable` (pasing in `CardData`) and transition to
 /// the `ReadingCard` state.
 ///
+/// Doc comments written before a transition are copied onto the variants of its events in the
+/// generated events enum, and listed in the transition's [TransitionInfo] (as its `doc`) for
+/// documentation tools. Other attributes can't go there, but can be put directly on an event
+/// variant inside the parentheses, e.g. `--(#[serde(rename = "swiped")] CardReadable(CardData))-->`.
+/// Those apply only to the variant, so `cfg` attributes, which would remove an event the rest of
+/// the generated code still refers to, aren't allowed. When an event appears in several
+/// transitions, the attributes from all of them are combined. States are documented on the types
+/// you define for them, which the generated state enum's variants link to.
+///
+/// A transition may apply to more than one state. Several source states can be listed separated
+/// by `|`, and `*` stands for every state of the machine:
+/// ```ignore
+/// ReadingCard | DoorOpen --(PowerLost)--> Locked;
+/// * --(Reset)--> Locked;
+/// ```
+/// A state which defines its own transition for an event is not affected by a `*` transition for
+/// that same event, so wildcards can be used for defaults which specific states override. Since
+/// the handler of a multi-source transition is called on whichever state the machine is in, every
+/// one of those state types must define it.
+///
+/// Likewise a single transition can be taken for several events, by separating them with `|`:
+/// ```ignore
+/// ReadingCard --(CardRejected | Timeout(Duration), on_fail)--> Locked;
+/// ```
+/// Since the handler of such a transition needs to be able to tell the events apart, it is passed
+/// the whole event (of the generated events enum type) rather than the variant's data.
+///
+/// A transition may also declare several destinations, separated by `|`, when which one is taken
+/// depends on the event. Its handler (which is required) then returns a transition to an enum of
+/// just those states, named as your machine with the destinations appended and joined by `Or`.
+/// That enum's transition alias is named the same way as the machine's. For example:
+/// ```ignore
+/// ReadingCard --(Verdict(bool), on_verdict)--> DoorOpen | Locked;
+///
+/// impl ReadingCard {
+///     fn on_verdict(self, accepted: bool) -> CardReaderDoorOpenOrLockedTransition {
+///         if accepted {
+///             TransitionResult::ok(vec![], DoorOpen {})
+///         } else {
+///             TransitionResult::ok(vec![], Locked {})
+///         }
+///     }
+/// }
+/// ```
+///
+/// Leaving out the arrow and destination makes a transition internal. Its handler, which is
+/// required, takes the state data by `&mut self`, may update it in place, and returns a `Result`
+/// of either some iterable collection of `CommandType` (usually a `Vec`) or an `ErrorType`. The
+/// machine then stays in the same state, without the state data being rebuilt:
+/// ```ignore
+/// ReadingCard --(CardDataChunk(Vec<u8>), on_chunk);
+/// ```
+///
+/// Event data may borrow, so that events can be handed to the machine straight out of a buffer
+/// without copying. Any lifetimes named in the events' data (other than `'static`) become lifetime
+/// parameters of the events enum, in the order they first appear, and handlers take the borrowed
+/// data as usual, returning owned commands and states:
+/// ```ignore
+/// Locked --(CardReadable(&'a [u8]), on_card_readable)--> ReadingCard;
+///
+/// impl Locked {
+///     fn on_card_readable(&self, data: &[u8]) -> CardReaderTransition {
+///         TransitionResult::ok(vec![Commands::ProcessData(data.to_vec())], ReadingCard {})
+///     }
+/// }
+/// ```
+/// The `proptest` option can't be used with borrowed events, since proptest only generates values
+/// which own their data.
+///
+/// Attributes can be added to the generated enums by putting them before the first line.
+/// Attributes there apply to the state enum, and the contents of an `#[events(...)]` attribute
+/// apply to the events enum:
+/// ```ignore
+/// fsm! {
+///     #[derive(Debug, Clone)]
+///     #[events(derive(Debug, Clone, PartialEq), serde(tag = "type"))]
+///     CardReader, Commands, Infallible
+///     ...
+/// }
+/// ```
+///
+/// Options for the generated code can be chosen with an `#[fsm(...)]` attribute in the same place.
+/// The available options are:
+/// * `proptest`: Implements proptest's `Arbitrary` for the events enum, so random sequences of
+///   events can be generated for use with the harness in `rustfsm::proptest_support`. Requires the
+///   `proptest` feature of `rustfsm`, the events enum to implement `Debug`, and the data of each
+///   event to implement `Arbitrary`.
+/// * `arbitrary`: Implements `arbitrary::Arbitrary` for the events enum, so fuzzers can generate
+///   streams of events for the harness in `rustfsm::fuzz`. Requires the `arbitrary` feature of
+///   `rustfsm`, and the data of each event to implement `Arbitrary`.
+/// * `command_sink`: Handlers take a `&mut dyn CommandSink<CommandType>` as their last argument,
+///   and emit commands into it rather than returning them (handlers of internal transitions
+///   return `Result<(), ErrorType>`). The generated `on_event_into` passes its caller's sink
+///   straight through, so transitions needn't allocate, while `on_event` collects the commands as
+///   usual. Any commands handlers still return go to the sink after those they emitted. Since
+///   commands reach the sink as soon as they're emitted, a handler which emits some and then fails
+///   leaves them in the sink.
+/// * `commands = CollectionType`: Collects the commands of each transition into a
+///   `CollectionType` rather than a `rustfsm::Commands` (a `Vec`). This is how machines without an
+///   allocator choose a fixed-capacity `rustfsm::CommandBuffer`, as in
+///   `#[fsm(commands = CommandBuffer<Commands, 4>)]`, which should have room for the most commands
+///   any one transition produces. Any type implementing `rustfsm::CommandCollection` will do.
+///
+/// When the `tracing` feature of `rustfsm` is enabled, every call to the generated `on_event` runs
+/// inside a [tracing](https://docs.rs/tracing) span named `transition`, with fields for the
+/// machine's name, the state it started in, the event, the state it ended up in, the outcome
+/// (`ok`, `invalid` or `error`), and the number of commands produced. Invalid transitions are also
+/// logged at warn level.
+///
+/// The generated code refers to everything it needs by its full path through the `rustfsm` crate,
+/// so that is the only dependency a crate defining machines needs, and nothing needs importing
+/// for the macro to work. It also only uses `core`, so machines can be defined in `#![no_std]`
+/// crates. Disable the default `std` feature of `rustfsm` there, keeping its `alloc` feature if an
+/// allocator is available. Without `alloc` there is no `Vec` for commands, so such machines choose
+/// a `CommandBuffer` with the `commands` option.
+///
 /// The macro will generate a few things:
 /// * An enum with a variant for each state, named with the provided name. In this case:
 ///   ```ignore
@@ -134,10 +259,22 @@ use syn::{
 ///   }
 ///   ```
 /// * An implementation of the [StateMachine](trait.StateMachine.html) trait for the generated state
-///   machine enum (in this case, `CardMachine`)
+///   machine enum (in this case, `CardMachine`). Events the current state has no transition for
+///   produce an `InvalidTransition` naming the state and event, and listing the events the state
+///   does accept.
 /// * A type alias for a [TransitionResult](enum.TransitionResult.html) with the appropriate generic
 ///   parameters set for your machine. It is named as your machine with `Transition` appended. In
 ///   this case, `CardMachineTransition`.
+/// * An implementation of the [MachineDefinition](trait.MachineDefinition.html) trait, describing
+///   the machine's states, events and transitions in the order they appear in the definition.
+/// * Fieldless enums identifying states and events without their data, named as your machine with
+///   `State` and `EventKind` appended. In this case, `CardMachineState` and
+///   `CardMachineEventKind`. They can be obtained with `state_kind()` on the machine and `kind()`
+///   on an event. The machine, events, and both of these have a `name()` method and a `Display`
+///   implementation, which give the name of the state or event. The machine and events enums also
+///   implement the [Named](trait.Named.html) trait, for generic code.
+/// * `available_events()` and `can_handle(&event)` methods on the machine, which tell you which
+///   events the machine's current state has transitions for.
 #[proc_macro]
 pub fn fsm(input: TokenStream) -> TokenStream {
     let def: StateMachineDefinition = parse_macro_input!(input as StateMachineDefinition);
@@ -148,13 +285,81 @@ struct StateMachineDefinition {
     name: Ident,
     command_type: Ident,
     error_type: Ident,
-    transitions: HashSet<Transition>,
+    transitions: Vec<Transition>,
+    /// Every state of the machine
+    states: Vec<Ident>,
+    /// Every event of the machine, with the attributes given to it in all of its transitions
+    events: Vec<Variant>,
+    /// The lifetimes borrowed by the events' data, which become parameters of the events enum
+    event_lifetimes: Vec<Lifetime>,
+    /// Attributes to put on the generated state enum
+    state_enum_attrs: Vec<Attribute>,
+    /// Attributes to put on the generated events enum (without the surrounding `#[...]`)
+    events_enum_attrs: Vec<proc_macro2::TokenStream>,
+    options: MachineOptions,
+}
+
+/// Optional extras for the generated code, chosen with an `#[fsm(...)]` attribute
+#[derive(Default)]
+struct MachineOptions {
+    /// Generate a proptest `Arbitrary` implementation for the events enum
+    proptest: bool,
+    /// Generate an `arbitrary::Arbitrary` implementation for the events enum
+    arbitrary: bool,
+    /// Pass handlers a `CommandSink` to emit commands into, and implement `on_event_into` with it
+    command_sink: bool,
+    /// The collection transitions gather their commands in, if not `rustfsm::Commands`
+    commands: Option<Type>,
+}
+
+impl MachineOptions {
+    fn add_from_attr(&mut self, attr: &Attribute) -> Result<()> {
+        attr.parse_args_with(|input: ParseStream| {
+            while !input.is_empty() {
+                let option: Ident = input.parse()?;
+                match option.to_string().as_str() {
+                    "proptest" => self.proptest = true,
+                    "arbitrary" => self.arbitrary = true,
+                    "command_sink" => self.command_sink = true,
+                    "commands" => {
+                        input.parse::<Token![=]>()?;
+                        self.commands = Some(input.parse()?);
+                    }
+                    _ => {
+                        return Err(Error::new(
+                            option.span(),
+                            format!("Unknown fsm option `{}`", option),
+                        ))
+                    }
+                }
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })
+    }
 }
 
 impl Parse for StateMachineDefinition {
     // TODO: Pub keyword
     fn parse(input: ParseStream) -> Result<Self> {
-        // First parse the state machine name, command type, and error type
+        // Attributes may precede the definition. They apply to the state enum, except for the
+        // contents of any `#[events(...)]` attributes, which apply to the events enum, and
+        // `#[fsm(...)]` attributes, which choose options for the generated code.
+        let mut state_enum_attrs = vec![];
+        let mut events_enum_attrs = vec![];
+        let mut options = MachineOptions::default();
+        for attr in input.call(Attribute::parse_outer)? {
+            if attr.path.is_ident("events") {
+                events_enum_attrs.extend(attr.parse_args_with(parse_comma_separated_attrs)?);
+            } else if attr.path.is_ident("fsm") {
+                options.add_from_attr(&attr)?;
+            } else {
+                state_enum_attrs.push(attr);
+            }
+        }
+        // Then parse the state machine name, command type, and error type
         let (name, command_type, error_type) = parse_first_line(&input).map_err(|mut e| {
             e.combine(Error::new(
                 e.span(),
@@ -164,18 +369,64 @@ impl Parse for StateMachineDefinition {
         })?;
         // Then the state machine definition is simply a sequence of transitions separated by
         // semicolons
-        let transitions: Punctuated<Transition, Token![;]> =
-            input.parse_terminated(Transition::parse)?;
-        let transitions = transitions.into_iter().collect();
+        let transitions: Punctuated<TransitionDef, Token![;]> =
+            input.parse_terminated(TransitionDef::parse)?;
+        let (transitions, states) = expand_transitions(transitions.into_iter().collect())?;
+        let events = collect_events(&transitions)?;
+        let event_lifetimes = collect_lifetimes(&events);
+        if options.proptest && !event_lifetimes.is_empty() {
+            return Err(Error::new(
+                event_lifetimes[0].span(),
+                "The `proptest` option needs events which own their data, but this one borrows it",
+            ));
+        }
+        // Generating an event needs at least one to pick from
+        for (enabled, option) in [
+            (options.arbitrary, "arbitrary"),
+            (options.proptest, "proptest"),
+        ] {
+            if enabled && events.is_empty() {
+                return Err(Error::new(
+                    name.span(),
+                    format!(
+                        "The `{}` option needs the machine to have at least one event",
+                        option
+                    ),
+                ));
+            }
+        }
         Ok(Self {
             name,
             transitions,
+            states,
+            events,
+            event_lifetimes,
             command_type,
             error_type,
+            state_enum_attrs,
+            events_enum_attrs,
+            options,
         })
     }
 }
 
+/// Splits the insides of an attribute like `#[events(derive(Debug), serde(tag = "type"))]` into
+/// the separate attributes it contains
+fn parse_comma_separated_attrs(input: ParseStream) -> Result<Vec<proc_macro2::TokenStream>> {
+    let mut attrs = vec![];
+    while !input.is_empty() {
+        let mut attr = proc_macro2::TokenStream::new();
+        while !input.is_empty() && !input.peek(Token![,]) {
+            attr.extend(std::iter::once(input.parse::<proc_macro2::TokenTree>()?));
+        }
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        attrs.push(attr);
+    }
+    Ok(attrs)
+}
+
 fn parse_first_line(input: &ParseStream) -> Result<(Ident, Ident, Ident)> {
     let name: Ident = input.parse()?;
     input.parse::<Token![,]>()?;
@@ -185,23 +436,232 @@ fn parse_first_line(input: &ParseStream) -> Result<(Ident, Ident, Ident)> {
     Ok((name, command_type, error_type))
 }
 
+/// Gathers the distinct events used by the transitions, in the order they first appear. Events
+/// may be used in several transitions, which must all agree on the event's data, and the
+/// attributes (like doc comments) from each of them are combined.
+fn collect_events(transitions: &[Transition]) -> Result<Vec<Variant>> {
+    let mut events: Vec<Variant> = vec![];
+    for event in transitions.iter().flat_map(|t| &t.events) {
+        match events.iter_mut().find(|e| e.ident == event.ident) {
+            Some(existing) => {
+                if existing.fields != event.fields {
+                    return Err(Error::new(
+                        event.span(),
+                        format!(
+                            "Event `{}` is used with different data in different transitions",
+                            event.ident
+                        ),
+                    ));
+                }
+                for attr in &event.attrs {
+                    if !existing.attrs.contains(attr) {
+                        existing.attrs.push(attr.clone());
+                    }
+                }
+            }
+            None => events.push(event.clone()),
+        }
+    }
+    Ok(events)
+}
+
+/// Gathers the lifetimes named in the events' data, other than `'static`, in the order they first
+/// appear
+fn collect_lifetimes(events: &[Variant]) -> Vec<Lifetime> {
+    fn visit(tokens: proc_macro2::TokenStream, lifetimes: &mut Vec<Lifetime>) {
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                proc_macro2::TokenTree::Punct(p) if p.as_char() == '\'' => {
+                    if let Some(proc_macro2::TokenTree::Ident(ident)) = tokens.peek() {
+                        let lifetime = Lifetime {
+                            apostrophe: p.span(),
+                            ident: ident.clone(),
+                        };
+                        if ident != "static" && !lifetimes.contains(&lifetime) {
+                            lifetimes.push(lifetime);
+                        }
+                        tokens.next();
+                    }
+                }
+                proc_macro2::TokenTree::Group(g) => visit(g.stream(), lifetimes),
+                _ => {}
+            }
+        }
+    }
+    let mut lifetimes = vec![];
+    for event in events {
+        let fields = &event.fields;
+        visit(quote! { #fields }, &mut lifetimes);
+    }
+    lifetimes
+}
+
+/// Turns the transitions as written into one transition per source state. Transitions listing
+/// several sources are split up, and wildcard transitions are added for every known state which
+/// doesn't already define its own transition for the same event. Also returns every state of the
+/// machine. Both are in the order they first appear in the definition. Apart from those wildcard
+/// overrides, a state may only have one transition for each event.
+fn expand_transitions(defs: Vec<TransitionDef>) -> Result<(Vec<Transition>, Vec<Ident>)> {
+    let mut states: Vec<Ident> = vec![];
+    for def in &defs {
+        let from = match &def.from {
+            TransitionSources::Any => &[][..],
+            TransitionSources::States(from) => from.as_slice(),
+        };
+        for s in from.iter().chain(def.to.iter().flatten()) {
+            if !states.contains(s) {
+                states.push(s.clone());
+            }
+        }
+    }
+    // The transitions each state defines explicitly, which take precedence over wildcards
+    let specific: Vec<(&Ident, &Ident)> = defs
+        .iter()
+        .flat_map(|def| match &def.from {
+            TransitionSources::Any => vec![],
+            TransitionSources::States(from) => from
+                .iter()
+                .flat_map(|f| def.events.iter().map(move |e| (f, &e.ident)))
+                .collect(),
+        })
+        .collect();
+
+    let mut transitions = vec![];
+    // The events each state has a transition for so far
+    let mut handled: Vec<(Ident, Ident)> = vec![];
+    for def in &defs {
+        let from = match &def.from {
+            TransitionSources::Any => &states,
+            TransitionSources::States(from) => from,
+        };
+        for f in from {
+            // Wildcards only cover the events this state doesn't already handle itself
+            let events: Vec<_> = def
+                .events
+                .iter()
+                .filter(|e| {
+                    def.from != TransitionSources::Any || !specific.contains(&(f, &e.ident))
+                })
+                .cloned()
+                .collect();
+            if events.is_empty() {
+                continue;
+            }
+            for event in &events {
+                let pair = (f.clone(), event.ident.clone());
+                if handled.contains(&pair) {
+                    return Err(Error::new(
+                        event.span(),
+                        format!(
+                            "State `{}` already has a transition for event `{}`",
+                            f, event.ident
+                        ),
+                    ));
+                }
+                handled.push(pair);
+            }
+            transitions.push(Transition {
+                from: f.clone(),
+                to: def.to.clone().unwrap_or_else(|| vec![f.clone()]),
+                events,
+                handler: def.handler.clone(),
+                handler_takes_event: def.events.len() > 1,
+                internal: def.to.is_none(),
+                doc: def.doc.clone(),
+            });
+        }
+    }
+    Ok((transitions, states))
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct Transition {
     from: Ident,
-    to: Ident,
-    event: Variant,
+    /// The possible destination states. Usually there is exactly one, but a transition may declare
+    /// several, in which case its handler picks which one is used.
+    to: Vec<Ident>,
+    events: Vec<Variant>,
+    handler: Option<Ident>,
+    /// True if the transition was defined with more than one event, in which case the handler is
+    /// passed the whole event rather than the variant's data
+    handler_takes_event: bool,
+    /// True if the transition is internal: the handler mutates the state data in place and the
+    /// machine stays in the same state (in which case `to` is just `from`)
+    internal: bool,
+    /// The doc comments written before the transition
+    doc: Option<String>,
+}
+
+/// The states a transition, as written in the definition, applies to
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum TransitionSources {
+    /// `*` - the transition applies to every state (unless the state overrides it)
+    Any,
+    /// `A | B | C` - the transition applies to each of the listed states
+    States(Vec<Ident>),
+}
+
+impl Parse for TransitionSources {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![*]) {
+            input.parse::<Token![*]>()?;
+            return Ok(Self::Any);
+        }
+        let mut states = vec![input.parse()?];
+        while input.peek(Token![|]) {
+            input.parse::<Token![|]>()?;
+            states.push(input.parse()?);
+        }
+        Ok(Self::States(states))
+    }
+}
+
+/// A single transition line as it appears in the definition, before it is expanded into one
+/// [Transition] per source state
+struct TransitionDef {
+    from: TransitionSources,
+    /// The destination state(s), or `None` for internal transitions
+    to: Option<Vec<Ident>>,
+    events: Vec<Variant>,
     handler: Option<Ident>,
+    /// The transition's doc comments, one line each
+    doc: Option<String>,
 }
 
-impl Parse for Transition {
+impl Parse for TransitionDef {
     fn parse(input: ParseStream) -> Result<Self> {
         // TODO: Use keywords instead of implicit placement, or other better arg-passing method
         //  maybe `enum MachineName<Command, Error>`
         //  and need start state
         // TODO: Currently the handlers are not required to transition to the state they claimed
         //   they would. It would be great to find a way to fix that.
-        // Parse the initial state name
-        let from: Ident = input.parse()?;
+        // Doc comments before the transition apply to its events, and describe the transition
+        let attrs = input.call(Attribute::parse_outer)?;
+        let mut doc = vec![];
+        for attr in &attrs {
+            match attr.parse_meta() {
+                Ok(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(line),
+                    ..
+                })) if path.is_ident("doc") => doc.push(line.value().trim().to_string()),
+                _ => {
+                    return Err(Error::new(
+                        attr.span(),
+                        "Only doc comments can be written before a transition; put other \
+                         attributes on the event inside the parentheses",
+                    ))
+                }
+            }
+        }
+        let doc = if doc.is_empty() {
+            None
+        } else {
+            Some(doc.join("\n"))
+        };
+        // Parse the initial state name(s)
+        let from: TransitionSources = input.parse()?;
         // Parse at least one dash
         input.parse::<Token![-]>()?;
         while input.peek(Token![-]) {
@@ -210,25 +670,14 @@ impl Parse for Transition {
         // Parse transition information inside parens
         let transition_info;
         parenthesized!(transition_info in input);
-        // Get the event variant definition
-        let event: Variant = transition_info.parse()?;
-        // Reject non-unit or single-item-tuple variants
-        match &event.fields {
-            Fields::Named(_) => {
-                return Err(Error::new(
-                    event.span(),
-                    "Struct variants are not supported for events",
-                ))
-            }
-            Fields::Unnamed(uf) => {
-                if uf.unnamed.len() != 1 {
-                    return Err(Error::new(
-                        event.span(),
-                        "Only tuple variants with exactly one item are supported for events",
-                    ));
-                }
-            }
-            Fields::Unit => {}
+        // Get the event variant definition(s), which are separated by `|`
+        let mut events = vec![parse_event_variant(&transition_info)?];
+        while transition_info.peek(Token![|]) {
+            transition_info.parse::<Token![|]>()?;
+            events.push(parse_event_variant(&transition_info)?);
+        }
+        for event in &mut events {
+            event.attrs.extend(attrs.iter().cloned());
         }
         // Check if there is an event handler, and parse it
         let handler = if transition_info.peek(Token![,]) {
@@ -237,136 +686,238 @@ impl Parse for Transition {
         } else {
             None
         };
+        // Transitions without an arrow and destination are internal, and must have a handler
+        // since there is no new state to construct
+        if input.is_empty() || input.peek(Token![;]) {
+            if handler.is_none() {
+                return Err(Error::new(
+                    events[0].span(),
+                    "Internal transitions (those without a destination) must have a handler",
+                ));
+            }
+            return Ok(Self {
+                from,
+                events,
+                handler,
+                to: None,
+                doc,
+            });
+        }
         // Parse at least one dash followed by the "arrow"
         input.parse::<Token![-]>()?;
         while input.peek(Token![-]) {
             input.parse::<Token![-]>()?;
         }
         input.parse::<Token![>]>()?;
-        // Parse the destination state
-        let to: Ident = input.parse()?;
+        // Parse the destination state, or states separated by `|` if the handler gets to choose
+        let mut to: Vec<Ident> = vec![input.parse()?];
+        while input.peek(Token![|]) {
+            input.parse::<Token![|]>()?;
+            to.push(input.parse()?);
+        }
+        // There's no way of picking a destination without a handler
+        if to.len() > 1 && handler.is_none() {
+            return Err(Error::new(
+                to[1].span(),
+                "Transitions with more than one destination must have a handler",
+            ));
+        }
+        let to = Some(to);
 
         Ok(Self {
             from,
-            event,
+            events,
             handler,
             to,
+            doc,
         })
     }
 }
 
+fn parse_event_variant(input: ParseStream) -> Result<Variant> {
+    let event: Variant = input.parse()?;
+    // The rest of the generated code refers to every event, so they can't be compiled out
+    if let Some(attr) = event
+        .attrs
+        .iter()
+        .find(|a| a.path.is_ident("cfg") || a.path.is_ident("cfg_attr"))
+    {
+        return Err(Error::new(
+            attr.span(),
+            "Events can't have `cfg` or `cfg_attr` attributes",
+        ));
+    }
+    // Reject non-unit or single-item-tuple variants
+    match &event.fields {
+        Fields::Named(_) => {
+            return Err(Error::new(
+                event.span(),
+                "Struct variants are not supported for events",
+            ))
+        }
+        Fields::Unnamed(uf) => {
+            if uf.unnamed.len() != 1 {
+                return Err(Error::new(
+                    event.span(),
+                    "Only tuple variants with exactly one item are supported for events",
+                ));
+            }
+        }
+        Fields::Unit => {}
+    }
+    Ok(event)
+}
+
 impl StateMachineDefinition {
     fn codegen(&self) -> TokenStream {
-        // First extract all of the states into a set, and build the enum's insides
-        let states: HashSet<_> = self
-            .transitions
-            .iter()
-            .flat_map(|t| vec![t.from.clone(), t.to.clone()])
-            .collect();
+        // First build the state enum's insides
+        let states = &self.states;
         let state_variants = states.iter().map(|s| {
+            // The state's data type is where it's documented, so point there
+            let doc = format!("The machine is in the [`{}`] state", s);
             quote! {
+                #[doc = #doc]
                 #s(#s)
             }
         });
         let name = &self.name;
+        let name_str = name.to_string();
+        let state_enum_attrs = &self.state_enum_attrs;
+        let state_from_impls = Self::from_impls(name, states);
         let main_enum = quote! {
-            #[derive(::derive_more::From)]
+            #(#state_enum_attrs)*
             pub enum #name {
                 #(#state_variants),*
             }
+
+            #state_from_impls
         };
 
         // Build the events enum
-        let events: HashSet<Variant> = self.transitions.iter().map(|t| t.event.clone()).collect();
-        let events_enum_name = Ident::new(&format!("{}Events", name), name.span());
-        let events: Vec<_> = events.into_iter().collect();
+        let events = &self.events;
+        let events_enum_name = self.events_enum_name();
+        let events_generics = self.events_generics();
+        let events_type = self.events_type();
+        let events_enum_attrs = &self.events_enum_attrs;
         let events_enum = quote! {
-            pub enum #events_enum_name {
+            #(#[#events_enum_attrs])*
+            pub enum #events_enum_name #events_generics {
                 #(#events),*
             }
         };
 
         // Construct the trait implementation
         let cmd_type = &self.command_type;
+        let commands_type = self.commands_type();
         let err_type = &self.error_type;
-        let mut statemap: HashMap<Ident, Vec<Transition>> = HashMap::new();
-        for t in &self.transitions {
-            statemap
-                .entry(t.from.clone())
-                .and_modify(|v| v.push(t.clone()))
-                .or_insert(vec![t.clone()]);
-        }
-        // Add any states without any transitions to the map
-        for s in &states {
-            if !statemap.contains_key(s) {
-                statemap.insert(s.clone(), vec![]);
-            }
-        }
-        let state_branches = statemap.iter().map(|(from, transitions)| {
+        let state_branches = states.iter().map(|from| {
+            let transitions: Vec<_> = self
+                .transitions
+                .iter()
+                .filter(|t| &t.from == from)
+                .collect();
+            let from_str = from.to_string();
+            let accepted = self.handled_events(from).into_iter().map(|e| e.to_string());
             let event_branches = transitions
                 .iter()
                 .map(|ts| {
-                    let ev_variant = &ts.event.ident;
-                    if let Some(ts_fn) = ts.handler.clone() {
-                        let span = ts_fn.span();
-                        match ts.event.fields {
-                            Fields::Unnamed(_) => quote_spanned! {span=>
-                                #events_enum_name::#ev_variant(val) => {
-                                    state_data.#ts_fn(val)
-                                }
-                            },
-                            Fields::Unit => quote_spanned! {span=>
-                                #events_enum_name::#ev_variant => {
-                                    state_data.#ts_fn()
-                                }
-                            },
-                            Fields::Named(_) => unreachable!(),
-                        }
-                    } else {
-                        // If events do not have a handler, attempt to construct the next state
-                        // using `Default`.
-                        let new_state = ts.to.clone();
-                        let span = new_state.span();
-                        let default_trans = quote_spanned! {span=>
-                            TransitionResult::default::<#new_state>()
-                        };
-                        let span = ts.event.span();
-                        match ts.event.fields {
-                            Fields::Unnamed(_) => quote_spanned! {span=>
-                                #events_enum_name::#ev_variant(_val) => {
-                                    #default_trans
-                                }
-                            },
-                            Fields::Unit => quote_spanned! {span=>
-                                #events_enum_name::#ev_variant => {
-                                    #default_trans
-                                }
-                            },
-                            Fields::Named(_) => unreachable!(),
+                    let (pattern, body) = self.transition_arm(ts);
+                    quote! {
+                        #pattern => {
+                            #body
                         }
                     }
                 })
-                // Since most states won't handle every possible event, return an error to that effect
-                .chain(std::iter::once(
-                    quote! { _ => { return TransitionResult::InvalidTransition } },
-                ));
+                // Since most states won't handle every possible event, return an error to that
+                // effect, describing what the state does accept
+                .chain(std::iter::once(quote! {
+                    #[allow(unreachable_patterns)]
+                    _ => {
+                        return ::rustfsm::TransitionResult::InvalidTransition(
+                            ::rustfsm::InvalidTransition {
+                                machine: ::rustfsm::Name::new(#name_str),
+                                state: ::rustfsm::Name::new(#from_str),
+                                event: ::rustfsm::Name::new(event.name()),
+                                accepted: {
+                                    const ACCEPTED: &[::rustfsm::Name] =
+                                        &[#(::rustfsm::Name::new(#accepted)),*];
+                                    ::rustfsm::NameList::new(ACCEPTED)
+                                },
+                            }
+                        )
+                    }
+                }));
+            // Internal transitions need to be able to mutate the state data
+            let binding = if transitions.iter().any(|t| t.internal) {
+                quote! { mut state_data }
+            } else {
+                quote! { state_data }
+            };
             quote! {
-                #name::#from(state_data) => match event {
+                #name::#from(#binding) => match event {
                     #(#event_branches),*
                 }
             }
         });
 
-        let trait_impl = quote! {
-            impl ::state_machine_trait::StateMachine<#name, #events_enum_name, #cmd_type> for #name {
-                type Error = #err_type;
+        let dispatch = quote! {
+            match self {
+                #(#state_branches),*
+            }
+        };
+        let on_event_body = self.traced(dispatch);
+        let event_methods = if self.options.command_sink {
+            // Handlers emit commands straight into the sink, so `on_event` is the one needing to
+            // collect them
+            quote! {
+                fn on_event(self, event: #events_type)
+                  -> ::rustfsm::TransitionResult<#name, Self::Error, #cmd_type, #commands_type> {
+                    let mut commands = <#commands_type as ::core::default::Default>::default();
+                    match ::rustfsm::StateMachine::on_event_into(self, event, &mut commands) {
+                        ::core::result::Result::Ok(new_state) => {
+                            ::rustfsm::TransitionResult::Ok { commands, new_state }
+                        }
+                        ::core::result::Result::Err(
+                            ::rustfsm::TransitionError::InvalidTransition(i)
+                        ) => ::rustfsm::TransitionResult::InvalidTransition(i),
+                        ::core::result::Result::Err(::rustfsm::TransitionError::Handler(e)) => {
+                            ::rustfsm::TransitionResult::Err(e)
+                        }
+                    }
+                }
 
-                fn on_event(self, event: #events_enum_name)
-                  -> TransitionResult<#name, Self::Error, #cmd_type> {
-                    match self {
-                        #(#state_branches),*
+                fn on_event_into(
+                    self,
+                    event: #events_type,
+                    sink: &mut dyn ::rustfsm::CommandSink<#cmd_type>,
+                ) -> ::core::result::Result<#name, ::rustfsm::TransitionError<Self::Error>> {
+                    // The dispatch returns early for invalid transitions, so run it in a closure,
+                    // handing it a reborrow of the sink so the sink's still usable afterwards
+                    let result: ::rustfsm::TransitionResult<#name, Self::Error, #cmd_type, #commands_type> = {
+                        let sink = &mut *sink;
+                        (move || { #on_event_body })()
+                    };
+                    // Handlers may still return some commands themselves
+                    let (new_state, commands) = result.into_result()?;
+                    for command in commands {
+                        ::rustfsm::CommandSink::emit(sink, command);
                     }
+                    ::core::result::Result::Ok(new_state)
+                }
+            }
+        } else {
+            quote! {
+                fn on_event(self, event: #events_type)
+                  -> ::rustfsm::TransitionResult<#name, Self::Error, #cmd_type, #commands_type> {
+                    #on_event_body
                 }
+            }
+        };
+        let trait_impl = quote! {
+            impl #events_generics ::rustfsm::StateMachine<#name, #events_type, #cmd_type, #commands_type> for #name {
+                type Error = #err_type;
+
+                #event_methods
 
                 fn state(&self) -> &Self {
                     &self
@@ -376,16 +927,593 @@ impl StateMachineDefinition {
 
         let transition_result_name = Ident::new(&format!("{}Transition", name), name.span());
         let transition_type_alias = quote! {
-            type #transition_result_name = TransitionResult<#name, #err_type, #cmd_type>;
+            type #transition_result_name = ::rustfsm::TransitionResult<#name, #err_type, #cmd_type, #commands_type>;
+        };
+
+        // Transitions with several possible destinations get an enum of just those destinations,
+        // (and a transition alias for it) so their handlers can't pick any other state
+        let mut destination_sets = BTreeMap::new();
+        for t in self.transitions.iter().filter(|t| t.to.len() > 1) {
+            let set_name = t.to.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let set_name = format!("{}{}", name, set_name.join("Or"));
+            destination_sets
+                .entry(set_name)
+                .or_insert_with(|| t.to.clone());
+        }
+        let destination_enums = destination_sets.iter().map(|(set_name, to)| {
+            let set_name = Ident::new(set_name, name.span());
+            let set_transition_name = Ident::new(&format!("{}Transition", set_name), name.span());
+            let doc = format!("The states a transition of [`{}`] can choose between", name);
+            let variant_docs = to.iter().map(|s| format!("The [`{}`] state", s));
+            let set_from_impls = Self::from_impls(&set_name, to);
+            quote! {
+                type #set_transition_name = ::rustfsm::TransitionResult<#set_name, #err_type, #cmd_type, #commands_type>;
+
+                #[doc = #doc]
+                pub enum #set_name {
+                    #(#[doc = #variant_docs] #to(#to)),*
+                }
+
+                #set_from_impls
+
+                impl ::core::convert::From<#set_name> for #name {
+                    fn from(s: #set_name) -> Self {
+                        match s {
+                            #(#set_name::#to(s) => #name::#to(s)),*
+                        }
+                    }
+                }
+            }
+        });
+
+        let definition_impl = self.definition_impl();
+        let kinds = self.kinds();
+        let event_queries = self.event_queries();
+        let proptest_impl = if self.options.proptest {
+            self.proptest_impl()
+        } else {
+            quote! {}
+        };
+        let arbitrary_impl = if self.options.arbitrary {
+            self.arbitrary_impl()
+        } else {
+            quote! {}
         };
 
         let output = quote! {
             #transition_type_alias
             #main_enum
+            #(#destination_enums)*
             #events_enum
             #trait_impl
+            #definition_impl
+            #kinds
+            #event_queries
+            #proptest_impl
+            #arbitrary_impl
         };
 
         output.into()
     }
+
+    /// The pattern matching a transition's event(s) in the events enum, and the code making the
+    /// transition, which expects the state's data in `state_data`
+    fn transition_arm(
+        &self,
+        ts: &Transition,
+    ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let events_enum_name = self.events_enum_name();
+        // With the `command_sink` option, handlers get the sink as their last argument
+        let (sink_only, sink_after) = if self.options.command_sink {
+            (quote! { sink }, quote! { , sink })
+        } else {
+            (quote! {}, quote! {})
+        };
+        // Work out the pattern matching the transition's event(s), and the call to
+        // its handler (if there is one)
+        let (pattern, handler_call) = if ts.handler_takes_event {
+            // Transitions defined with several events get one arm matching all of
+            // them, and hand the whole event to the handler so it can tell them apart
+            let patterns = ts.events.iter().map(|ev| {
+                let ev_variant = &ev.ident;
+                match ev.fields {
+                    Fields::Unnamed(_) => quote! { #events_enum_name::#ev_variant(_) },
+                    Fields::Unit => quote! { #events_enum_name::#ev_variant },
+                    Fields::Named(_) => unreachable!(),
+                }
+            });
+            let handler_call = ts.handler.as_ref().map(|ts_fn| {
+                let span = ts_fn.span();
+                quote_spanned! {span=> state_data.#ts_fn(event #sink_after) }
+            });
+            (quote! { event @ (#(#patterns)|*) }, handler_call)
+        } else {
+            let event = &ts.events[0];
+            let ev_variant = &event.ident;
+            let span = event.span();
+            match (&event.fields, &ts.handler) {
+                (Fields::Unnamed(_), Some(ts_fn)) => (
+                    quote_spanned! {span=> #events_enum_name::#ev_variant(val) },
+                    Some(quote_spanned! {ts_fn.span()=> state_data.#ts_fn(val #sink_after) }),
+                ),
+                (Fields::Unnamed(_), None) => (
+                    quote_spanned! {span=> #events_enum_name::#ev_variant(_val) },
+                    None,
+                ),
+                (Fields::Unit, ts_fn) => (
+                    quote_spanned! {span=> #events_enum_name::#ev_variant },
+                    ts_fn.as_ref().map(|ts_fn| {
+                        quote_spanned! {ts_fn.span()=> state_data.#ts_fn(#sink_only) }
+                    }),
+                ),
+                (Fields::Named(_), _) => unreachable!(),
+            }
+        };
+        let body = match handler_call {
+            // Internal transitions mutate the state data in place, and the machine
+            // stays in the same state. With a sink, their commands have already gone
+            // to it.
+            Some(call) if ts.internal && self.options.command_sink => quote! {
+                match #call {
+                    ::core::result::Result::Ok(()) => {
+                        ::rustfsm::TransitionResult::ok(::core::iter::empty(), state_data)
+                    }
+                    ::core::result::Result::Err(e) => ::rustfsm::TransitionResult::Err(e),
+                }
+            },
+            Some(call) if ts.internal => quote! {
+                match #call {
+                    ::core::result::Result::Ok(commands) => {
+                        ::rustfsm::TransitionResult::ok(commands, state_data)
+                    }
+                    ::core::result::Result::Err(e) => ::rustfsm::TransitionResult::Err(e),
+                }
+            },
+            // Handlers choosing between several destinations return a transition to
+            // the enum of those destinations, which then gets converted into the
+            // machine's own enum
+            Some(call) if ts.to.len() > 1 => quote! {
+                match ::rustfsm::TransitionResult::from(#call) {
+                    ::rustfsm::TransitionResult::Ok { commands, new_state } => {
+                        ::rustfsm::TransitionResult::Ok {
+                            commands,
+                            new_state: ::core::convert::From::from(new_state),
+                        }
+                    }
+                    ::rustfsm::TransitionResult::InvalidTransition(i) => {
+                        ::rustfsm::TransitionResult::InvalidTransition(i)
+                    }
+                    ::rustfsm::TransitionResult::Err(e) => {
+                        ::rustfsm::TransitionResult::Err(e)
+                    }
+                }
+            },
+            // Handlers may also return a `Result` of a transition, which the
+            // conversion flattens
+            Some(call) => quote! { ::rustfsm::TransitionResult::from(#call) },
+            None => {
+                // If events do not have a handler, attempt to construct the next
+                // state using `Default`.
+                let new_state = ts.to[0].clone();
+                let span = new_state.span();
+                quote_spanned! {span=>
+                    ::rustfsm::TransitionResult::default::<#new_state>()
+                }
+            }
+        };
+        (pattern, body)
+    }
+
+    /// `From` impls converting each of `states` into the variant of the enum `name` holding it
+    fn from_impls(name: &Ident, states: &[Ident]) -> proc_macro2::TokenStream {
+        quote! {
+            #(
+                impl ::core::convert::From<#states> for #name {
+                    fn from(state: #states) -> Self {
+                        #name::#states(state)
+                    }
+                }
+            )*
+        }
+    }
+
+    /// Wraps the dispatch in `rustfsm`'s `__fsm_transition!`, which traces the transition if
+    /// `rustfsm`'s `tracing` feature is enabled
+    fn traced(&self, dispatch: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let name_str = name.to_string();
+        let cmd_type = &self.command_type;
+        // With the `command_sink` option, handlers emit most commands into the sink rather than
+        // returning them, so those are counted on their way through
+        let sink = if self.options.command_sink {
+            quote! { sink: sink: #cmd_type, }
+        } else {
+            quote! {}
+        };
+        quote! {
+            ::rustfsm::__fsm_transition!(
+                machine: #name_str,
+                from: #name::name(&self),
+                event: event.name(),
+                to: |new_state| #name::name(new_state),
+                #sink
+                dispatch: { #dispatch },
+            )
+        }
+    }
+
+    /// The collection transitions gather their commands in
+    fn commands_type(&self) -> proc_macro2::TokenStream {
+        match &self.options.commands {
+            Some(commands) => quote! { #commands },
+            None => {
+                let cmd_type = &self.command_type;
+                quote! { ::rustfsm::Commands<#cmd_type> }
+            }
+        }
+    }
+
+    fn events_enum_name(&self) -> Ident {
+        Ident::new(&format!("{}Events", self.name), self.name.span())
+    }
+
+    /// The lifetime parameters of the events enum, if its events borrow any data
+    fn events_generics(&self) -> proc_macro2::TokenStream {
+        if self.event_lifetimes.is_empty() {
+            quote! {}
+        } else {
+            let lifetimes = &self.event_lifetimes;
+            quote! { <#(#lifetimes),*> }
+        }
+    }
+
+    /// The type of the events enum, with its lifetime parameters
+    fn events_type(&self) -> proc_macro2::TokenStream {
+        let events_enum_name = self.events_enum_name();
+        let events_generics = self.events_generics();
+        quote! { #events_enum_name #events_generics }
+    }
+
+    /// The type of the events enum, with any lifetimes elided
+    fn events_type_elided(&self) -> proc_macro2::TokenStream {
+        let events_enum_name = self.events_enum_name();
+        if self.event_lifetimes.is_empty() {
+            quote! { #events_enum_name }
+        } else {
+            let elided = self.event_lifetimes.iter().map(|_| quote! { '_ });
+            quote! { #events_enum_name<#(#elided),*> }
+        }
+    }
+
+    fn state_kind_name(&self) -> Ident {
+        Ident::new(&format!("{}State", self.name), self.name.span())
+    }
+
+    fn event_kind_name(&self) -> Ident {
+        Ident::new(&format!("{}EventKind", self.name), self.name.span())
+    }
+
+    /// Generates fieldless enums identifying the machine's states and events without their data,
+    /// along with ways to get them and their names from the state and events enums
+    fn kinds(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let events_enum_name = self.events_enum_name();
+        let events_generics = self.events_generics();
+        let events_type = self.events_type();
+        let state_kind_name = self.state_kind_name();
+        let event_kind_name = self.event_kind_name();
+        let states = &self.states;
+        let state_names: Vec<_> = states.iter().map(|s| s.to_string()).collect();
+        let events: Vec<_> = self.events.iter().map(|e| &e.ident).collect();
+        let event_names: Vec<_> = events.iter().map(|e| e.to_string()).collect();
+        let state_indices = 0..states.len();
+        let event_indices = 0..events.len();
+        let event_patterns = self.events.iter().map(|e| {
+            let ev_variant = &e.ident;
+            match e.fields {
+                Fields::Unnamed(_) => quote! { #events_enum_name::#ev_variant(_) },
+                Fields::Unit => quote! { #events_enum_name::#ev_variant },
+                Fields::Named(_) => unreachable!(),
+            }
+        });
+        let state_kind_doc = format!("The states of [`{}`], without their data", name);
+        let event_kind_doc = format!("The events of [`{}`], without their data", name);
+        quote! {
+            #[doc = #state_kind_doc]
+            #[derive(
+                ::core::fmt::Debug,
+                ::core::clone::Clone,
+                ::core::marker::Copy,
+                ::core::cmp::PartialEq,
+                ::core::cmp::Eq,
+                ::core::hash::Hash,
+                ::core::cmp::PartialOrd,
+                ::core::cmp::Ord,
+            )]
+            pub enum #state_kind_name {
+                #(#[doc = #state_names] #states),*
+            }
+
+            impl #state_kind_name {
+                /// The name of the state
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        #(#state_kind_name::#states => #state_names),*
+                    }
+                }
+            }
+
+            impl ::core::fmt::Display for #state_kind_name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+
+            #[doc = #event_kind_doc]
+            #[derive(
+                ::core::fmt::Debug,
+                ::core::clone::Clone,
+                ::core::marker::Copy,
+                ::core::cmp::PartialEq,
+                ::core::cmp::Eq,
+                ::core::hash::Hash,
+                ::core::cmp::PartialOrd,
+                ::core::cmp::Ord,
+            )]
+            pub enum #event_kind_name {
+                #(#[doc = #event_names] #events),*
+            }
+
+            impl #event_kind_name {
+                /// The name of the event
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        #(#event_kind_name::#events => #event_names),*
+                    }
+                }
+            }
+
+            impl ::core::fmt::Display for #event_kind_name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+
+            impl #name {
+                /// The state the machine is in, without its data
+                pub fn state_kind(&self) -> #state_kind_name {
+                    match self {
+                        #(#name::#states(_) => #state_kind_name::#states),*
+                    }
+                }
+
+                /// The name of the state the machine is in
+                pub fn name(&self) -> &'static str {
+                    self.state_kind().name()
+                }
+            }
+
+            impl ::rustfsm::Named for #name {
+                fn name(&self) -> &::rustfsm::Name {
+                    const NAMES: &[::rustfsm::Name] = &[#(::rustfsm::Name::new(#state_names)),*];
+                    match self.state_kind() {
+                        #(#state_kind_name::#states => &NAMES[#state_indices]),*
+                    }
+                }
+            }
+
+            /// Displays the name of the state the machine is in
+            impl ::core::fmt::Display for #name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+
+            impl #events_generics #events_type {
+                /// The kind of the event, without its data
+                pub fn kind(&self) -> #event_kind_name {
+                    match self {
+                        #(#event_patterns => #event_kind_name::#events),*
+                    }
+                }
+
+                /// The name of the event
+                pub fn name(&self) -> &'static str {
+                    self.kind().name()
+                }
+            }
+
+            impl #events_generics ::rustfsm::Named for #events_type {
+                fn name(&self) -> &::rustfsm::Name {
+                    const NAMES: &[::rustfsm::Name] = &[#(::rustfsm::Name::new(#event_names)),*];
+                    match self.kind() {
+                        #(#event_kind_name::#events => &NAMES[#event_indices]),*
+                    }
+                }
+            }
+
+            /// Displays the name of the event
+            impl #events_generics ::core::fmt::Display for #events_type {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+        }
+    }
+
+    /// Generates methods for asking which events the machine can handle in its current state
+    fn event_queries(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let events_type = self.events_type_elided();
+        let event_kind_name = self.event_kind_name();
+        let state_branches = self.states.iter().map(|state| {
+            let handled = self.handled_events(state);
+            quote! {
+                #name::#state(_) => &[#(#event_kind_name::#handled),*]
+            }
+        });
+        quote! {
+            impl #name {
+                /// The kinds of events the machine's current state has transitions for
+                pub fn available_events(&self) -> &'static [#event_kind_name] {
+                    match self {
+                        #(#state_branches),*
+                    }
+                }
+
+                /// Returns true if the machine's current state has a transition for the event.
+                /// The transition's handler may of course still fail.
+                pub fn can_handle(&self, event: &#events_type) -> bool {
+                    self.available_events().contains(&event.kind())
+                }
+            }
+        }
+    }
+
+    /// The events `state` has transitions for, in the order they first appear
+    fn handled_events(&self, state: &Ident) -> Vec<&Ident> {
+        let mut handled = vec![];
+        for t in self.transitions.iter().filter(|t| &t.from == state) {
+            for e in &t.events {
+                if !handled.contains(&&e.ident) {
+                    handled.push(&e.ident);
+                }
+            }
+        }
+        handled
+    }
+
+    /// Generates a proptest `Arbitrary` implementation for the events enum, which picks one of the
+    /// events, and generates its data (if any) using that type's own `Arbitrary` implementation
+    fn proptest_impl(&self) -> proc_macro2::TokenStream {
+        let events_enum_name = self.events_enum_name();
+        let strategies = self.events.iter().map(|e| {
+            let ev_variant = &e.ident;
+            match &e.fields {
+                Fields::Unnamed(uf) => {
+                    let ty = &uf.unnamed[0].ty;
+                    quote! {
+                        ::rustfsm::proptest_support::proptest::strategy::Strategy::boxed(
+                            ::rustfsm::proptest_support::proptest::strategy::Strategy::prop_map(
+                                ::rustfsm::proptest_support::proptest::arbitrary::any::<#ty>(),
+                                #events_enum_name::#ev_variant,
+                            )
+                        )
+                    }
+                }
+                Fields::Unit => quote! {
+                    ::rustfsm::proptest_support::proptest::strategy::Strategy::boxed(
+                        ::rustfsm::proptest_support::proptest::strategy::Just(
+                            #events_enum_name::#ev_variant
+                        )
+                    )
+                },
+                Fields::Named(_) => unreachable!(),
+            }
+        });
+        quote! {
+            impl ::rustfsm::proptest_support::proptest::arbitrary::Arbitrary
+                for #events_enum_name
+            {
+                type Parameters = ();
+                type Strategy = ::rustfsm::proptest_support::proptest::strategy::BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    ::rustfsm::proptest_support::proptest::strategy::Strategy::boxed(
+                        ::rustfsm::proptest_support::proptest::strategy::Union::new(
+                            [#(#strategies),*]
+                        )
+                    )
+                }
+            }
+        }
+    }
+
+    /// Generates an `arbitrary::Arbitrary` implementation for the events enum, which uses the input
+    /// to pick one of the events, and then generates its data (if any) from the rest of the input
+    fn arbitrary_impl(&self) -> proc_macro2::TokenStream {
+        let events_enum_name = self.events_enum_name();
+        // Borrowed event data borrows from the fuzzer's input
+        let input_lifetimes = self.event_lifetimes.iter().map(|_| quote! { 'a });
+        let last_index = self.events.len() as u32 - 1;
+        let branches = self.events.iter().enumerate().map(|(i, e)| {
+            let i = i as u32;
+            let ev_variant = &e.ident;
+            match e.fields {
+                Fields::Unnamed(_) => quote! {
+                    #i => #events_enum_name::#ev_variant(
+                        ::rustfsm::fuzz::arbitrary::Arbitrary::arbitrary(u)?
+                    )
+                },
+                Fields::Unit => quote! { #i => #events_enum_name::#ev_variant },
+                Fields::Named(_) => unreachable!(),
+            }
+        });
+        quote! {
+            impl<'a> ::rustfsm::fuzz::arbitrary::Arbitrary<'a>
+                for #events_enum_name<#(#input_lifetimes),*>
+            {
+                fn arbitrary(
+                    u: &mut ::rustfsm::fuzz::arbitrary::Unstructured<'a>,
+                ) -> ::rustfsm::fuzz::arbitrary::Result<Self> {
+                    ::core::result::Result::Ok(match u.int_in_range(0..=#last_index)? {
+                        #(#branches,)*
+                        _ => ::core::unreachable!(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Generates the implementation of `MachineDefinition`, describing the machine
+    fn definition_impl(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let name_str = name.to_string();
+        let state_names = self.states.iter().map(|s| s.to_string());
+        let event_names = self.events.iter().map(|e| e.ident.to_string());
+        let transition_infos = self.transitions.iter().flat_map(|t| {
+            let from = t.from.to_string();
+            let handler = match &t.handler {
+                Some(h) => {
+                    let h = h.to_string();
+                    quote! { ::core::option::Option::Some(#h) }
+                }
+                None => quote! { ::core::option::Option::None },
+            };
+            let internal = t.internal;
+            let doc = match &t.doc {
+                Some(d) => quote! { ::core::option::Option::Some(#d) },
+                None => quote! { ::core::option::Option::None },
+            };
+            t.events.iter().flat_map(move |e| {
+                let event = e.ident.to_string();
+                let from = from.clone();
+                let handler = handler.clone();
+                let doc = doc.clone();
+                t.to.iter().map(move |to| {
+                    let to = to.to_string();
+                    quote! {
+                        ::rustfsm::TransitionInfo {
+                            from: ::rustfsm::Name::new(#from),
+                            event: ::rustfsm::Name::new(#event),
+                            to: ::rustfsm::Name::new(#to),
+                            handler: #handler,
+                            internal: #internal,
+                            doc: #doc,
+                        }
+                    }
+                })
+            })
+        });
+        quote! {
+            impl ::rustfsm::MachineDefinition for #name {
+                const NAME: &'static ::rustfsm::Name = &::rustfsm::Name::new(#name_str);
+                const STATES: &'static [::rustfsm::Name] = &[#(::rustfsm::Name::new(#state_names)),*];
+                const EVENTS: &'static [::rustfsm::Name] = &[#(::rustfsm::Name::new(#event_names)),*];
+                const TRANSITIONS: &'static [::rustfsm::TransitionInfo] = &[
+                    #(#transition_infos),*
+                ];
+            }
+        }
+    }
 }
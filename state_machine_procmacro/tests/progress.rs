@@ -1,4 +1,4 @@
-use state_machine_trait::TransitionResult;
+use rustfsm::TransitionResult;
 use std::convert::Infallible;
 
 #[test]
@@ -9,7 +9,7 @@ fn tests() {
 }
 
 //Kept here to inspect manual expansion
-state_machine_procmacro::fsm! {
+rustfsm::fsm! {
     SimpleMachine, SimpleMachineCommand, Infallible
 
     One --(A(String), foo)--> Two;
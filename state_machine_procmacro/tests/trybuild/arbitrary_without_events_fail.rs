@@ -0,0 +1,8 @@
+use rustfsm::fsm;
+
+fsm! {
+    #[fsm(arbitrary)]
+    Empty, EmptyCmd, Infallible
+}
+
+fn main() {}
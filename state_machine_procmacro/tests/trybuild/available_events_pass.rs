@@ -0,0 +1,46 @@
+use rustfsm::fsm;
+use rustfsm::StateMachine;
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(String))--> Two;
+    One --(B | C)--> One;
+    Two --(B)--> One;
+    * --(Reset)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+
+#[derive(Default)]
+pub struct Two {}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {
+    let sm = SimpleMachine::One(One {});
+    assert_eq!(
+        sm.available_events(),
+        &[
+            SimpleMachineEventKind::A,
+            SimpleMachineEventKind::B,
+            SimpleMachineEventKind::C,
+            SimpleMachineEventKind::Reset
+        ]
+    );
+    assert!(sm.can_handle(&SimpleMachineEvents::A("hi".to_string())));
+    assert!(sm.can_handle(&SimpleMachineEvents::C));
+
+    let (sm, _) = sm
+        .on_event(SimpleMachineEvents::A("hi".to_string()))
+        .unwrap();
+    assert_eq!(
+        sm.available_events(),
+        &[SimpleMachineEventKind::B, SimpleMachineEventKind::Reset]
+    );
+    assert!(!sm.can_handle(&SimpleMachineEvents::A("hi".to_string())));
+    assert!(!sm.can_handle(&SimpleMachineEvents::C));
+    assert!(sm.can_handle(&SimpleMachineEvents::Reset));
+}
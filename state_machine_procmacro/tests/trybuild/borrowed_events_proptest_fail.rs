@@ -0,0 +1,10 @@
+use rustfsm::fsm;
+
+fsm! {
+    #[fsm(proptest)]
+    Simple, SimpleCmd, Infallible
+
+    One --(A(&'a str))--> Two
+}
+
+fn main() {}
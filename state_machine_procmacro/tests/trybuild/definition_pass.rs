@@ -0,0 +1,79 @@
+use rustfsm::fsm;
+use rustfsm::{MachineDefinition, Name, TransitionInfo, TransitionResult};
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(String), on_a)--> Two | Three;
+    /// Goes back
+    /// to the start
+    Two --(B | C)--> One;
+    Three --(D, on_d);
+    * --(Reset)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+impl One {
+    fn on_a(self, _: String) -> SimpleMachineTwoOrThreeTransition {
+        TransitionResult::ok(vec![], Two {})
+    }
+}
+
+#[derive(Default)]
+pub struct Two {}
+
+#[derive(Default)]
+pub struct Three {}
+impl Three {
+    fn on_d(&mut self) -> Result<Vec<SimpleMachineCommand>, Infallible> {
+        Ok(vec![])
+    }
+}
+
+pub enum SimpleMachineCommand {}
+
+fn info(
+    from: &'static str,
+    event: &'static str,
+    to: &'static str,
+    handler: Option<&'static str>,
+) -> TransitionInfo {
+    TransitionInfo {
+        from: Name::new(from),
+        event: Name::new(event),
+        to: Name::new(to),
+        handler,
+        internal: false,
+        doc: None,
+    }
+}
+
+fn main() {
+    assert_eq!(SimpleMachine::NAME, "SimpleMachine");
+    assert_eq!(SimpleMachine::STATES, &["One", "Two", "Three"]);
+    assert_eq!(SimpleMachine::EVENTS, &["A", "B", "C", "D", "Reset"]);
+    assert_eq!(
+        SimpleMachine::TRANSITIONS,
+        &[
+            info("One", "A", "Two", Some("on_a")),
+            info("One", "A", "Three", Some("on_a")),
+            TransitionInfo {
+                doc: Some("Goes back\nto the start"),
+                ..info("Two", "B", "One", None)
+            },
+            TransitionInfo {
+                doc: Some("Goes back\nto the start"),
+                ..info("Two", "C", "One", None)
+            },
+            TransitionInfo {
+                internal: true,
+                ..info("Three", "D", "Three", Some("on_d"))
+            },
+            info("One", "Reset", "One", None),
+            info("Two", "Reset", "One", None),
+            info("Three", "Reset", "One", None),
+        ]
+    );
+}
@@ -0,0 +1,10 @@
+use rustfsm::fsm;
+
+fsm! {
+    Simple, SimpleCmd, Infallible
+
+    One | Two --(A)--> Three;
+    One --(A)--> Two
+}
+
+fn main() {}
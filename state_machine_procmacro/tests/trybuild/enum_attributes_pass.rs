@@ -0,0 +1,34 @@
+use rustfsm::fsm;
+use rustfsm::StateMachine;
+use std::convert::Infallible;
+
+fsm! {
+    /// A machine with some derives
+    #[derive(Debug, Clone, PartialEq)]
+    #[events(derive(Debug, Clone, PartialEq, Eq, Hash), allow(dead_code))]
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(String))--> Two;
+    Two --(B)--> One
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct One {}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Two {}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {
+    let event = SimpleMachineEvents::A("hi".to_string());
+    assert_eq!(event.clone(), event);
+    assert_eq!(format!("{:?}", event), "A(\"hi\")");
+    let mut events = std::collections::HashSet::new();
+    events.insert(SimpleMachineEvents::B);
+
+    let sm = SimpleMachine::One(One {});
+    let (sm, _) = sm.clone().on_event(event).unwrap();
+    assert_eq!(sm, SimpleMachine::Two(Two {}));
+    assert_eq!(format!("{:?}", sm), "Two(Two)");
+}
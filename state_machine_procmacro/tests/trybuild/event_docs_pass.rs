@@ -0,0 +1,38 @@
+#![deny(missing_docs)]
+//! Every generated event variant must end up documented for this to compile
+
+use rustfsm::fsm;
+use rustfsm::TransitionResult;
+use std::convert::Infallible;
+
+fsm! {
+    /// A documented machine
+    #[events(doc = "The machine's events")]
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    /// Moves from one to two
+    One --(A(String))--> Two;
+    Two --(#[doc = "Moves from two to one"] B)--> One;
+    /// Also moves from one to two
+    One --(B)--> Two;
+    /// Picks where to go
+    Two --(C, on_c)--> One | Two
+}
+
+/// First state
+#[derive(Default)]
+pub struct One {}
+
+/// Second state
+#[derive(Default)]
+pub struct Two {}
+impl Two {
+    fn on_c(self) -> SimpleMachineOneOrTwoTransition {
+        TransitionResult::ok(vec![], self)
+    }
+}
+
+/// Commands
+pub enum SimpleMachineCommand {}
+
+fn main() {}
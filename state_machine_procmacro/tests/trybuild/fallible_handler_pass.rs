@@ -0,0 +1,54 @@
+use rustfsm::{fsm, StateMachine, TransitionResult};
+
+fsm! {
+    Parser, ParserCommand, ParseError
+
+    Waiting --(Input(String), on_input) --> Parsed;
+    Parsed --(Choose(String), on_choose) --> Waiting | Parsed
+}
+
+#[derive(Default)]
+pub struct Waiting {}
+impl Waiting {
+    fn on_input(&self, input: String) -> Result<ParserTransition, ParseError> {
+        let value = input.parse().map_err(|_| ParseError)?;
+        Ok(TransitionResult::ok(vec![ParserCommand::Parsed(value)], Parsed {}))
+    }
+}
+
+#[derive(Default)]
+pub struct Parsed {}
+impl Parsed {
+    fn on_choose(&self, input: String) -> Result<ParserWaitingOrParsedTransition, ParseError> {
+        if input.parse::<u32>().map_err(|_| ParseError)? == 0 {
+            Ok(TransitionResult::default::<Waiting>())
+        } else {
+            Ok(TransitionResult::default::<Parsed>())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParserCommand {
+    Parsed(u32),
+}
+
+#[derive(Debug)]
+pub struct ParseError;
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "not a number")
+    }
+}
+impl std::error::Error for ParseError {}
+
+fn main() {
+    let sm = Parser::Waiting(Waiting {});
+    assert!(sm.on_event(ParserEvents::Input("nope".to_string())).is_err());
+
+    let sm = Parser::Waiting(Waiting {});
+    let (sm, cmds) = sm.on_event(ParserEvents::Input("7".to_string())).unwrap();
+    assert_eq!(cmds, vec![ParserCommand::Parsed(7)]);
+    let (sm, _) = sm.on_event(ParserEvents::Choose("0".to_string())).unwrap();
+    assert!(matches!(sm, Parser::Waiting(_)));
+}
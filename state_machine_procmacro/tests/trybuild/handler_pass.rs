@@ -1,5 +1,4 @@
-use state_machine_procmacro::fsm;
-use state_machine_trait::TransitionResult;
+use rustfsm::fsm;
 use std::convert::Infallible;
 
 fsm! {
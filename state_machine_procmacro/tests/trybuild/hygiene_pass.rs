@@ -0,0 +1,63 @@
+//! The generated code shouldn't depend on anything being imported, or on what the names it uses
+//! mean where the macro is called
+#![allow(dead_code, unused_macros)]
+
+use std::convert::Infallible;
+
+struct TransitionResult;
+struct Ok;
+struct Err;
+struct Some;
+struct None;
+trait From {}
+trait Into {}
+macro_rules! unreachable {
+    () => {
+        compile_error!("The generated code used the caller's `unreachable!`")
+    };
+}
+
+rustfsm::fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(String), foo)--> Two;
+    One --(B)--> Two;
+    Two --(C, choose) --> One | Two;
+    Two --(D, count)
+}
+
+#[derive(Default)]
+pub struct One {}
+impl One {
+    fn foo(self, _: String) -> SimpleMachineTransition {
+        rustfsm::TransitionResult::ok(vec![SimpleMachineCommand::Moved], Two::default())
+    }
+}
+
+#[derive(Default)]
+pub struct Two {
+    count: u32,
+}
+impl Two {
+    fn choose(self) -> SimpleMachineOneOrTwoTransition {
+        rustfsm::TransitionResult::default::<One>()
+    }
+    fn count(&mut self) -> Result<Vec<SimpleMachineCommand>, Infallible> {
+        self.count += 1;
+        Result::Ok(vec![])
+    }
+}
+
+pub enum SimpleMachineCommand {
+    Moved,
+}
+
+fn main() {
+    use rustfsm::StateMachine;
+
+    let sm = SimpleMachine::One(One {});
+    let (sm, _) = sm.on_event(SimpleMachineEvents::A("hi".to_string())).unwrap();
+    let (sm, _) = sm.on_event(SimpleMachineEvents::D).unwrap();
+    let (sm, _) = sm.on_event(SimpleMachineEvents::C).unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+}
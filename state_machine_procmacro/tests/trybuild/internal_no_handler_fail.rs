@@ -0,0 +1,10 @@
+use rustfsm::fsm;
+
+fsm! {
+    Simple, SimpleCmd, Infallible
+
+    One --(A);
+    One --(B)--> Two
+}
+
+fn main() {}
@@ -0,0 +1,62 @@
+use rustfsm::fsm;
+use rustfsm::{StateMachine, TransitionResult};
+
+fsm! {
+    Counter, CounterCommand, CounterError
+
+    Counting --(Add(u32), on_add);
+    Counting --(Reset | Tick, on_other);
+    Counting --(Stop)--> Stopped
+}
+
+#[derive(Default)]
+pub struct Counting {
+    count: u32,
+}
+impl Counting {
+    fn on_add(&mut self, amount: u32) -> Result<Vec<CounterCommand>, CounterError> {
+        self.count = self.count.checked_add(amount).ok_or(CounterError::Overflow)?;
+        Ok(vec![CounterCommand::Report(self.count)])
+    }
+    fn on_other(&mut self, event: CounterEvents) -> Result<Vec<CounterCommand>, CounterError> {
+        if let CounterEvents::Reset = event {
+            self.count = 0;
+        }
+        Ok(vec![])
+    }
+}
+
+#[derive(Default)]
+pub struct Stopped {}
+
+#[derive(Debug, PartialEq)]
+pub enum CounterCommand {
+    Report(u32),
+}
+
+#[derive(Debug)]
+pub enum CounterError {
+    Overflow,
+}
+impl std::fmt::Display for CounterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "counter overflowed")
+    }
+}
+impl std::error::Error for CounterError {}
+
+fn main() {
+    let sm = Counter::Counting(Counting::default());
+    let (sm, cmds) = sm.on_event(CounterEvents::Add(2)).unwrap();
+    assert_eq!(cmds, vec![CounterCommand::Report(2)]);
+    let (sm, cmds) = sm.on_event(CounterEvents::Add(3)).unwrap();
+    assert_eq!(cmds, vec![CounterCommand::Report(5)]);
+    let (sm, _) = sm.on_event(CounterEvents::Tick).unwrap();
+    let (sm, _) = sm.on_event(CounterEvents::Reset).unwrap();
+    let (sm, cmds) = sm.on_event(CounterEvents::Add(u32::MAX)).unwrap();
+    assert_eq!(cmds, vec![CounterCommand::Report(u32::MAX)]);
+    assert!(matches!(
+        sm.on_event(CounterEvents::Add(1)),
+        TransitionResult::Err(CounterError::Overflow)
+    ));
+}
@@ -0,0 +1,40 @@
+use rustfsm::fsm;
+use rustfsm::StateMachine;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(String))--> Two;
+    Two --(B)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+
+#[derive(Default)]
+pub struct Two {}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {
+    let event = SimpleMachineEvents::A("data".to_string());
+    assert_eq!(event.kind(), SimpleMachineEventKind::A);
+    assert_eq!(event.name(), "A");
+    assert_eq!(event.to_string(), "A");
+    assert_eq!(SimpleMachineEventKind::B.to_string(), "B");
+
+    let sm = SimpleMachine::One(One {});
+    assert_eq!(sm.state_kind(), SimpleMachineState::One);
+    assert_eq!(sm.name(), "One");
+    let (sm, _) = sm.on_event(event).unwrap();
+    assert_eq!(sm.state_kind(), SimpleMachineState::Two);
+    assert_eq!(sm.to_string(), "Two");
+    assert_eq!(SimpleMachineState::Two.name(), "Two");
+
+    // Kinds can be used as map keys
+    let mut visits = HashMap::new();
+    *visits.entry(sm.state_kind()).or_insert(0) += 1;
+    assert_eq!(visits[&SimpleMachineState::Two], 1);
+}
@@ -1,5 +1,5 @@
-use state_machine_procmacro::fsm;
-use state_machine_trait::TransitionResult;
+use rustfsm::fsm;
+use rustfsm::TransitionResult;
 use std::convert::Infallible;
 
 fsm! {
@@ -0,0 +1,10 @@
+use rustfsm::fsm;
+
+fsm! {
+    Simple, SimpleCmd, Infallible
+
+    One --(A(String))--> Two;
+    Two --(A(u8))--> One
+}
+
+fn main() {}
@@ -0,0 +1,40 @@
+use rustfsm::fsm;
+use rustfsm::{StateMachine, TransitionResult};
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(bool), on_a)--> Two | Three;
+    Two | Three --(B)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+impl One {
+    fn on_a(self, go_to_two: bool) -> SimpleMachineTwoOrThreeTransition {
+        if go_to_two {
+            TransitionResult::ok(vec![], Two {})
+        } else {
+            TransitionResult::ok(vec![], Three {})
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Two {}
+
+#[derive(Default)]
+pub struct Three {}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {
+    let (sm, _) = SimpleMachine::One(One {})
+        .on_event(SimpleMachineEvents::A(true))
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::Two(_)));
+    let (sm, _) = sm.on_event(SimpleMachineEvents::B).unwrap();
+    let (sm, _) = sm.on_event(SimpleMachineEvents::A(false)).unwrap();
+    assert!(matches!(sm, SimpleMachine::Three(_)));
+}
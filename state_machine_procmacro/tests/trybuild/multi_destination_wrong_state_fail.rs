@@ -0,0 +1,28 @@
+use rustfsm::fsm;
+use rustfsm::TransitionResult;
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A(bool), on_a)--> Two | Three;
+    Two | Three --(B)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+impl One {
+    fn on_a(self, _: bool) -> SimpleMachineTwoOrThreeTransition {
+        TransitionResult::ok(vec![], One {})
+    }
+}
+
+#[derive(Default)]
+pub struct Two {}
+
+#[derive(Default)]
+pub struct Three {}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {}
@@ -0,0 +1,42 @@
+use rustfsm::fsm;
+use rustfsm::{StateMachine, TransitionResult};
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A | B(u8), on_a_or_b)--> Two;
+    Two --(A | C)--> One
+}
+
+#[derive(Default)]
+pub struct One {}
+impl One {
+    fn on_a_or_b(self, event: SimpleMachineEvents) -> SimpleMachineTransition {
+        match event {
+            SimpleMachineEvents::B(val) => {
+                TransitionResult::ok(vec![SimpleMachineCommand::GotB(val)], Two {})
+            }
+            _ => TransitionResult::default::<Two>(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Two {}
+
+pub enum SimpleMachineCommand {
+    GotB(u8),
+}
+
+fn main() {
+    let (sm, cmds) = SimpleMachine::One(One {})
+        .on_event(SimpleMachineEvents::B(5))
+        .unwrap();
+    assert!(matches!(cmds[0], SimpleMachineCommand::GotB(5)));
+    let (sm, _) = sm.on_event(SimpleMachineEvents::C).unwrap();
+    let (sm, cmds) = sm.on_event(SimpleMachineEvents::A).unwrap();
+    assert!(cmds.is_empty());
+    let (sm, _) = sm.on_event(SimpleMachineEvents::A).unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+}
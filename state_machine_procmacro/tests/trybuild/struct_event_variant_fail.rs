@@ -1,4 +1,4 @@
-use state_machine_procmacro::fsm;
+use rustfsm::fsm;
 
 fsm! {
     Simple, SimpleCommand, Infallible
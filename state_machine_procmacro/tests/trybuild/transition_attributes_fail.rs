@@ -0,0 +1,16 @@
+use rustfsm::fsm;
+
+fsm! {
+    Simple, SimpleCmd, Infallible
+
+    #[cfg(any())]
+    One --(A)--> Two
+}
+
+fsm! {
+    Other, OtherCmd, Infallible
+
+    One --(#[cfg(any())] A)--> Two
+}
+
+fn main() {}
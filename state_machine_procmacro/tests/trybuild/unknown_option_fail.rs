@@ -0,0 +1,10 @@
+use rustfsm::fsm;
+
+fsm! {
+    #[fsm(quickcheck)]
+    Simple, SimpleCmd, Infallible
+
+    One --(A)--> Two
+}
+
+fn main() {}
@@ -0,0 +1,54 @@
+use rustfsm::fsm;
+use rustfsm::{StateMachine, TransitionResult};
+use std::convert::Infallible;
+
+fsm! {
+    SimpleMachine, SimpleMachineCommand, Infallible
+
+    One --(A)--> Two;
+    Two | Three --(B)--> One;
+    * --(Reset)--> One;
+    Three --(Reset, on_reset)--> Two
+}
+
+#[derive(Default)]
+pub struct One {}
+
+#[derive(Default)]
+pub struct Two {}
+
+#[derive(Default)]
+pub struct Three {}
+impl Three {
+    fn on_reset(self) -> SimpleMachineTransition {
+        TransitionResult::default::<Two>()
+    }
+}
+
+pub enum SimpleMachineCommand {}
+
+fn main() {
+    // Both listed sources handle `B`
+    let (sm, _) = SimpleMachine::Three(Three {})
+        .on_event(SimpleMachineEvents::B)
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+    let (sm, _) = SimpleMachine::Two(Two {})
+        .on_event(SimpleMachineEvents::B)
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+    // The wildcard applies to every state...
+    let (sm, _) = SimpleMachine::Two(Two {})
+        .on_event(SimpleMachineEvents::Reset)
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+    let (sm, _) = SimpleMachine::One(One {})
+        .on_event(SimpleMachineEvents::Reset)
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::One(_)));
+    // ...except the ones which override it
+    let (sm, _) = SimpleMachine::Three(Three {})
+        .on_event(SimpleMachineEvents::Reset)
+        .unwrap();
+    assert!(matches!(sm, SimpleMachine::Two(_)));
+}
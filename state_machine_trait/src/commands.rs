@@ -0,0 +1,235 @@
+use core::{
+    fmt::{self, Debug, Formatter},
+    iter::FromIterator,
+};
+
+/// The collection of commands transitions produce unless their machine chooses another, with
+/// `fsm!`'s `commands` option. Only available with the `alloc` feature (enabled by default).
+#[cfg(feature = "alloc")]
+pub type Commands<C> = alloc::vec::Vec<C>;
+
+/// Names a collection of commands of type `C`, which is always the collection itself.
+/// [TransitionResult](crate::TransitionResult) holds its commands as one of these, since it needs
+/// to mention its command type somewhere other than in the default for its collection.
+#[doc(hidden)]
+pub trait CollectionOf<C> {
+    type Collection;
+}
+
+impl<C, CC> CollectionOf<C> for CC {
+    type Collection = CC;
+}
+
+/// A collection of the commands produced by a transition, like [Commands] or a [CommandBuffer].
+/// Which one a machine uses is the last type parameter of its [StateMachine](crate::StateMachine)
+/// implementation and its [TransitionResult](crate::TransitionResult)s.
+pub trait CommandCollection<C>:
+    Default + IntoIterator<Item = C> + FromIterator<C> + Extend<C> + CommandSink<C>
+{
+    /// The same kind of collection, holding commands of type `D`
+    type Of<D>: CommandCollection<D>;
+
+    /// The number of commands in the collection
+    fn len(&self) -> usize;
+
+    /// True if the collection holds no commands
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C> CommandCollection<C> for alloc::vec::Vec<C> {
+    type Of<D> = alloc::vec::Vec<D>;
+
+    fn len(&self) -> usize {
+        alloc::vec::Vec::len(self)
+    }
+}
+
+/// Collecting, extending or emitting more than `N` commands into the buffer panics, so `N` should
+/// be at least the most commands any one transition produces. [CommandBuffer::push] and
+/// [CommandBuffer::try_extend] report running out of room instead.
+impl<C, const N: usize> CommandCollection<C> for CommandBuffer<C, N> {
+    type Of<D> = CommandBuffer<D, N>;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// A fixed-capacity list of commands which never allocates, for use where there is no allocator
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CommandBuffer<C, const N: usize> {
+    items: [Option<C>; N],
+    len: usize,
+}
+
+impl<C, const N: usize> CommandBuffer<C, N> {
+    /// Creates an empty buffer
+    pub fn new() -> Self {
+        Self {
+            items: core::array::from_fn(|_| None),
+            len: 0,
+        }
+    }
+
+    /// Appends a command, handing it back if the buffer is already full
+    pub fn push(&mut self, command: C) -> Result<(), C> {
+        if self.len == N {
+            return Err(command);
+        }
+        self.items[self.len] = Some(command);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends commands in order until the buffer is full, handing back the first one which
+    /// didn't fit
+    pub fn try_extend<I: IntoIterator<Item = C>>(&mut self, commands: I) -> Result<(), C> {
+        commands
+            .into_iter()
+            .try_for_each(|command| self.push(command))
+    }
+
+    /// The number of commands in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the buffer holds no commands
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the commands in the order they were added
+    pub fn iter(&self) -> core::iter::Flatten<core::slice::Iter<'_, Option<C>>> {
+        self.items.iter().flatten()
+    }
+}
+
+impl<C, const N: usize> Default for CommandBuffer<C, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Debug, const N: usize> Debug for CommandBuffer<C, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Panics if the iterator yields more than `N` commands
+impl<C, const N: usize> FromIterator<C> for CommandBuffer<C, N> {
+    fn from_iter<I: IntoIterator<Item = C>>(iter: I) -> Self {
+        let mut buffer = Self::new();
+        buffer.extend(iter);
+        buffer
+    }
+}
+
+/// Panics if the buffer would end up with more than `N` commands
+impl<C, const N: usize> Extend<C> for CommandBuffer<C, N> {
+    fn extend<I: IntoIterator<Item = C>>(&mut self, iter: I) {
+        if self.try_extend(iter).is_err() {
+            panic!("A transition produced more than {} commands", N);
+        }
+    }
+}
+
+impl<C, const N: usize> IntoIterator for CommandBuffer<C, N> {
+    type Item = C;
+    type IntoIter = core::iter::Flatten<core::array::IntoIter<Option<C>, N>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterator::into_iter(self.items).flatten()
+    }
+}
+
+impl<'a, C, const N: usize> IntoIterator for &'a CommandBuffer<C, N> {
+    type Item = &'a C;
+    type IntoIter = core::iter::Flatten<core::slice::Iter<'a, Option<C>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Somewhere for a machine to send the commands it produces, instead of collecting them into a
+/// new [CommandCollection] for every transition. See [StateMachine::on_event_into](crate::StateMachine::on_event_into).
+pub trait CommandSink<C> {
+    /// Accepts a command produced by a transition
+    fn emit(&mut self, command: C);
+}
+
+impl<C, S: CommandSink<C> + ?Sized> CommandSink<C> for &mut S {
+    fn emit(&mut self, command: C) {
+        (**self).emit(command)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C> CommandSink<C> for alloc::vec::Vec<C> {
+    fn emit(&mut self, command: C) {
+        self.push(command)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C> CommandSink<C> for alloc::collections::VecDeque<C> {
+    fn emit(&mut self, command: C) {
+        self.push_back(command)
+    }
+}
+
+/// Panics if the buffer is already full
+impl<C, const N: usize> CommandSink<C> for CommandBuffer<C, N> {
+    fn emit(&mut self, command: C) {
+        self.extend(core::iter::once(command))
+    }
+}
+
+/// Commands sent after the receiver has hung up are dropped, since nothing is left to act on them
+#[cfg(feature = "std")]
+impl<C> CommandSink<C> for std::sync::mpsc::Sender<C> {
+    fn emit(&mut self, command: C) {
+        let _ = self.send(command);
+    }
+}
+
+/// A [CommandSink] which calls a function with each command
+#[derive(Debug, Clone, Copy)]
+pub struct FnSink<F>(pub F);
+
+impl<C, F: FnMut(C)> CommandSink<C> for FnSink<F> {
+    fn emit(&mut self, command: C) {
+        (self.0)(command)
+    }
+}
+
+/// Passes commands on to another sink, counting them. Used by the code `fsm!` generates to report
+/// how many commands a transition produced.
+#[doc(hidden)]
+pub struct CountingSink<'a, C> {
+    sink: &'a mut dyn CommandSink<C>,
+    count: usize,
+}
+
+impl<'a, C> CountingSink<'a, C> {
+    pub fn new(sink: &'a mut dyn CommandSink<C>) -> Self {
+        Self { sink, count: 0 }
+    }
+
+    /// The number of commands passed on so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<C> CommandSink<C> for CountingSink<'_, C> {
+    fn emit(&mut self, command: C) {
+        self.count += 1;
+        self.sink.emit(command)
+    }
+}
@@ -0,0 +1,146 @@
+//! Tracking which of a machine's declared transitions have actually been taken.
+//!
+//! Attach a [TransitionCoverage] to a machine by handing it events through
+//! [ObserveTransitions::on_event_observed](crate::observe::ObserveTransitions::on_event_observed),
+//! then check [TransitionCoverage::uncovered] or print the report once the tests are done.
+
+use crate::{
+    observe::{ObservedTransition, TransitionObserver, TransitionOutcome},
+    MachineDefinition, Name, TransitionInfo,
+};
+use std::fmt::{self, Display, Formatter, Write};
+
+/// Counts how many times each declared transition of a machine was taken
+#[derive(Debug, Clone)]
+pub struct TransitionCoverage {
+    machine: &'static Name,
+    transitions: Vec<(TransitionInfo, usize)>,
+    undeclared: Vec<(Name, Name, Name)>,
+}
+
+impl TransitionCoverage {
+    /// Creates a recorder for the transitions of machine type `M`, none of which are covered yet
+    pub fn new<M: MachineDefinition>() -> Self {
+        Self {
+            machine: M::NAME,
+            transitions: M::TRANSITIONS.iter().map(|t| (t.clone(), 0)).collect(),
+            undeclared: vec![],
+        }
+    }
+
+    /// Records that the machine went from `from` to `to` on `event`
+    pub fn record(&mut self, from: &Name, event: &Name, to: &Name) {
+        let declared = self
+            .transitions
+            .iter_mut()
+            .find(|(t, _)| t.from == *from && t.event == *event && t.to == *to);
+        match declared {
+            Some((_, hits)) => *hits += 1,
+            None => {
+                let undeclared = (from.clone(), event.clone(), to.clone());
+                if !self.undeclared.contains(&undeclared) {
+                    self.undeclared.push(undeclared);
+                }
+            }
+        }
+    }
+
+    /// Every declared transition, with the number of times it was taken
+    pub fn transitions(&self) -> &[(TransitionInfo, usize)] {
+        &self.transitions
+    }
+
+    /// The declared transitions which were never taken
+    pub fn uncovered(&self) -> Vec<&TransitionInfo> {
+        self.transitions
+            .iter()
+            .filter(|(_, hits)| *hits == 0)
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    /// Transitions which were taken but aren't declared in the machine's definition, as
+    /// `(from, event, to)`. These happen when a handler returns a state other than the one its
+    /// transition declared.
+    pub fn undeclared(&self) -> &[(Name, Name, Name)] {
+        &self.undeclared
+    }
+
+    /// The fraction of declared transitions which were taken, between 0 and 1
+    pub fn ratio(&self) -> f64 {
+        if self.transitions.is_empty() {
+            return 1.0;
+        }
+        let covered = self
+            .transitions
+            .iter()
+            .filter(|(_, hits)| *hits > 0)
+            .count();
+        covered as f64 / self.transitions.len() as f64
+    }
+
+    /// The report as JSON, for consumption by other tools. Since all the names involved are Rust
+    /// identifiers, none of them need escaping.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"machine\":\"{}\",\"transitions\":[", self.machine);
+        for (i, (t, hits)) in self.transitions.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"from\":\"{}\",\"event\":\"{}\",\"to\":\"{}\",\"hits\":{}}}",
+                t.from, t.event, t.to, hits
+            )
+            .unwrap();
+        }
+        json.push_str("],\"undeclared\":[");
+        for (i, (from, event, to)) in self.undeclared.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"from\":\"{}\",\"event\":\"{}\",\"to\":\"{}\"}}",
+                from, event, to
+            )
+            .unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+impl TransitionObserver for TransitionCoverage {
+    fn on_transition(&mut self, transition: &ObservedTransition) {
+        if transition.machine != *self.machine {
+            return;
+        }
+        if let TransitionOutcome::Ok { to, .. } = &transition.outcome {
+            self.record(&transition.from, &transition.event, to);
+        }
+    }
+}
+
+impl Display for TransitionCoverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Transition coverage for {}: {:.0}%",
+            self.machine,
+            self.ratio() * 100.0
+        )?;
+        for (t, hits) in &self.transitions {
+            let marker = if *hits == 0 { "MISSED" } else { "ok" };
+            writeln!(
+                f,
+                "  {:>6} {} --({})--> {} [{} hits]",
+                marker, t.from, t.event, t.to, hits
+            )?;
+        }
+        for (from, event, to) in &self.undeclared {
+            writeln!(f, "  UNDECLARED {} --({})--> {}", from, event, to)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,583 @@
+//! Machines whose states, events and transitions are only known at runtime.
+//!
+//! Where `fsm!` needs the whole machine written out at compile time, a [DynamicMachineBuilder]
+//! takes it piece by piece, such as from a configuration file. States and events are named with
+//! strings, or referred to by the [StateId]s and [EventId]s the builder hands out, and handlers are
+//! closures. The resulting [DynamicMachine] implements [StateMachine], [Named] and
+//! [DescribeMachine], so it works with the same tools as machines defined with `fsm!`:
+//! ```
+//! use state_machine_trait::{dynamic::DynamicMachine, StateMachine};
+//! use std::convert::Infallible;
+//!
+//! let mut builder = DynamicMachine::<String, Infallible, u32>::builder("CardReader");
+//! builder
+//!     .transition_with("Locked", "CardReadable", "ReadingCard", |card| {
+//!         Ok(vec![format!("Reading card {}", card)])
+//!     })
+//!     .transition("ReadingCard", "CardAccepted", "DoorOpen")
+//!     .transition("DoorOpen", "DoorClosed", "Locked");
+//! let reader = builder.build("Locked").unwrap();
+//!
+//! let event = reader.event("CardReadable", 7).unwrap();
+//! let (reader, commands) = reader.on_event(event).unwrap();
+//! assert_eq!(reader.name(), "ReadingCard");
+//! assert_eq!(commands, vec!["Reading card 7".to_string()]);
+//! ```
+//!
+//! Each name is allocated once, when it's first added, and then shared by the definition, the
+//! events and any [InvalidTransition]s or [TransitionInfo]s which mention it, so nothing outlives
+//! the last of them. Building again without changing the definition reuses the one already built.
+//! Definitions are meant to be built once, when the configuration is loaded, and then shared by
+//! every machine started from them, which [DynamicMachine::with_state] and cloning do.
+//!
+//! IDs and events belong to the builder which made them, and the machines built from it. Using
+//! them with another builder or its machines is an error, rather than quietly meaning whichever
+//! state or event happens to have the same index there.
+
+use crate::{
+    Commands, DescribeMachine, InvalidTransition, Name, NameList, Named, StateMachine,
+    TransitionInfo, TransitionResult,
+};
+use std::{
+    convert::Infallible,
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/// Hands out a different owner token to every builder
+static NEXT_OWNER: AtomicUsize = AtomicUsize::new(0);
+
+/// Identifies a state of a [DynamicMachineBuilder], and the machines built from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StateId {
+    owner: usize,
+    index: usize,
+}
+
+/// Identifies an event of a [DynamicMachineBuilder], and the machines built from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventId {
+    owner: usize,
+    index: usize,
+}
+
+/// The names of a builder's states or events
+#[doc(hidden)]
+pub struct Names {
+    owner: usize,
+    names: Vec<Name>,
+}
+
+impl Names {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| *n == name)
+    }
+
+    /// The index of the name, adding it if it's new
+    fn add(&mut self, name: &str) -> usize {
+        self.position(name).unwrap_or_else(|| {
+            self.names.push(Name::from(name.to_string()));
+            self.names.len() - 1
+        })
+    }
+}
+
+/// Refers to a state or event when building a machine: either by name, which adds it to the
+/// machine if it's new, or by the ID the builder gave it
+pub trait Key<Id> {
+    #[doc(hidden)]
+    fn resolve(self, names: &mut Names) -> Result<usize, DefinitionError>;
+
+    /// Like `resolve`, but names must already be known
+    #[doc(hidden)]
+    fn find(self, names: &Names) -> Result<usize, DefinitionError>;
+}
+
+impl<Id> Key<Id> for &str {
+    fn resolve(self, names: &mut Names) -> Result<usize, DefinitionError> {
+        Ok(names.add(self))
+    }
+
+    fn find(self, names: &Names) -> Result<usize, DefinitionError> {
+        names
+            .position(self)
+            .ok_or_else(|| DefinitionError::UnknownInitialState(self.to_string()))
+    }
+}
+
+impl<Id> Key<Id> for String {
+    fn resolve(self, names: &mut Names) -> Result<usize, DefinitionError> {
+        Ok(names.add(&self))
+    }
+
+    fn find(self, names: &Names) -> Result<usize, DefinitionError> {
+        Key::<Id>::find(self.as_str(), names)
+    }
+}
+
+impl Key<StateId> for StateId {
+    fn resolve(self, names: &mut Names) -> Result<usize, DefinitionError> {
+        self.find(names)
+    }
+
+    fn find(self, names: &Names) -> Result<usize, DefinitionError> {
+        if self.owner == names.owner {
+            Ok(self.index)
+        } else {
+            Err(DefinitionError::UnknownState(self))
+        }
+    }
+}
+
+impl Key<EventId> for EventId {
+    fn resolve(self, names: &mut Names) -> Result<usize, DefinitionError> {
+        self.find(names)
+    }
+
+    fn find(self, names: &Names) -> Result<usize, DefinitionError> {
+        if self.owner == names.owner {
+            Ok(self.index)
+        } else {
+            Err(DefinitionError::UnknownEvent(self))
+        }
+    }
+}
+
+/// Handles an event's data, producing the transition's commands
+type Handler<C, E, D> = Arc<dyn Fn(D) -> Result<Commands<C>, E> + Send + Sync>;
+
+/// Where a state goes on an event
+struct Target<C, E, D> {
+    to: usize,
+    handler: Option<Handler<C, E, D>>,
+}
+
+impl<C, E, D> Clone for Target<C, E, D> {
+    fn clone(&self) -> Self {
+        Self {
+            to: self.to,
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// Builds the definition of a [DynamicMachine]. Mistakes, like defining two transitions for the
+/// same state and event, are reported by [DynamicMachineBuilder::build].
+pub struct DynamicMachineBuilder<C, E = Infallible, D = ()> {
+    name: Name,
+    states: Names,
+    events: Names,
+    transitions: Vec<(usize, usize, Target<C, E, D>)>,
+    error: Option<DefinitionError>,
+    /// The definition as last built, until it changes
+    built: OnceLock<Arc<Definition<C, E, D>>>,
+}
+
+impl<C, E, D> DynamicMachineBuilder<C, E, D> {
+    /// Starts the definition of a machine called `name`, with no states or events
+    pub fn new(name: impl Into<String>) -> Self {
+        let owner = NEXT_OWNER.fetch_add(1, Ordering::Relaxed);
+        Self {
+            name: Name::from(name.into()),
+            states: Names {
+                owner,
+                names: vec![],
+            },
+            events: Names {
+                owner,
+                names: vec![],
+            },
+            transitions: vec![],
+            error: None,
+            built: OnceLock::new(),
+        }
+    }
+
+    /// Adds a state, if there isn't one by that name already, and returns its ID
+    pub fn state(&mut self, name: &str) -> StateId {
+        self.built.take();
+        StateId {
+            owner: self.states.owner,
+            index: self.states.add(name),
+        }
+    }
+
+    /// Adds an event, if there isn't one by that name already, and returns its ID
+    pub fn event(&mut self, name: &str) -> EventId {
+        self.built.take();
+        EventId {
+            owner: self.events.owner,
+            index: self.events.add(name),
+        }
+    }
+
+    /// Adds a transition from `from` to `to` on `event`, without a handler
+    pub fn transition(
+        &mut self,
+        from: impl Key<StateId>,
+        event: impl Key<EventId>,
+        to: impl Key<StateId>,
+    ) -> &mut Self {
+        self.add(from, event, to, None)
+    }
+
+    /// Adds a transition from `from` to `to` on `event`, calling `handler` with the event's data
+    /// to produce the transition's commands. If the handler returns an error, so does the
+    /// transition, and since [StateMachine::on_event] consumed the machine, it's gone; use
+    /// [TryEvent](crate::transaction::TryEvent) to keep the machine in case of errors.
+    pub fn transition_with<F>(
+        &mut self,
+        from: impl Key<StateId>,
+        event: impl Key<EventId>,
+        to: impl Key<StateId>,
+        handler: F,
+    ) -> &mut Self
+    where
+        F: Fn(D) -> Result<Commands<C>, E> + Send + Sync + 'static,
+    {
+        self.add(from, event, to, Some(Arc::new(handler)))
+    }
+
+    fn add(
+        &mut self,
+        from: impl Key<StateId>,
+        event: impl Key<EventId>,
+        to: impl Key<StateId>,
+        handler: Option<Handler<C, E, D>>,
+    ) -> &mut Self {
+        self.built.take();
+        let resolved = (|| {
+            let from = from.resolve(&mut self.states)?;
+            let event = event.resolve(&mut self.events)?;
+            let to = to.resolve(&mut self.states)?;
+            if self
+                .transitions
+                .iter()
+                .any(|(f, e, _)| *f == from && *e == event)
+            {
+                return Err(DefinitionError::DuplicateTransition {
+                    state: self.states.names[from].to_string(),
+                    event: self.events.names[event].to_string(),
+                });
+            }
+            Ok((from, event, to))
+        })();
+        match resolved {
+            Ok((from, event, to)) => self.transitions.push((from, event, Target { to, handler })),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Finishes the definition, returning a machine in the `initial` state. Further machines can be
+    /// started from the same definition with [DynamicMachine::with_state].
+    pub fn build(
+        &self,
+        initial: impl Key<StateId>,
+    ) -> Result<DynamicMachine<C, E, D>, DefinitionError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        // The initial state must already be known, rather than added by naming it here
+        let initial = initial.find(&self.states)?;
+        Ok(DynamicMachine {
+            definition: self.built.get_or_init(|| self.definition()).clone(),
+            state: initial,
+        })
+    }
+
+    fn definition(&self) -> Arc<Definition<C, E, D>> {
+        let states = self.states.names.clone();
+        let events = self.events.names.clone();
+        let mut table = vec![vec![None; events.len()]; states.len()];
+        let mut accepted = vec![vec![]; states.len()];
+        let mut transitions = vec![];
+        for (from, event, target) in &self.transitions {
+            table[*from][*event] = Some(target.clone());
+            accepted[*from].push(events[*event].clone());
+            transitions.push(TransitionInfo {
+                from: states[*from].clone(),
+                event: events[*event].clone(),
+                to: states[target.to].clone(),
+                // Closures don't have names
+                handler: None,
+                internal: false,
+                doc: None,
+            });
+        }
+        let accepted = accepted.into_iter().map(NameList::from).collect();
+
+        Arc::new(Definition {
+            owner: self.states.owner,
+            name: self.name.clone(),
+            states,
+            events,
+            transitions,
+            table,
+            accepted,
+        })
+    }
+}
+
+/// The shared definition of a dynamic machine
+struct Definition<C, E, D> {
+    /// The token of the builder the definition came from, which its IDs and events carry
+    owner: usize,
+    name: Name,
+    states: Vec<Name>,
+    events: Vec<Name>,
+    transitions: Vec<TransitionInfo>,
+    /// The target of each state's transition for each event, if it has one
+    table: Vec<Vec<Option<Target<C, E, D>>>>,
+    /// The events each state has transitions for
+    accepted: Vec<NameList>,
+}
+
+/// A machine defined at runtime with a [DynamicMachineBuilder]. Its commands are `C`, its handlers'
+/// errors are `E`, and the data its events carry is `D`.
+pub struct DynamicMachine<C, E = Infallible, D = ()> {
+    definition: Arc<Definition<C, E, D>>,
+    state: usize,
+}
+
+impl<C, E, D> DynamicMachine<C, E, D> {
+    /// Starts the definition of a machine called `name`
+    pub fn builder(name: impl Into<String>) -> DynamicMachineBuilder<C, E, D> {
+        DynamicMachineBuilder::new(name)
+    }
+
+    /// A machine with the same definition as this one, in the state called `state`, if there is
+    /// one
+    pub fn with_state(&self, state: &str) -> Option<Self> {
+        let state = self.definition.states.iter().position(|s| *s == state)?;
+        Some(Self {
+            definition: self.definition.clone(),
+            state,
+        })
+    }
+
+    /// The ID of the state the machine is in
+    pub fn state_id(&self) -> StateId {
+        StateId {
+            owner: self.definition.owner,
+            index: self.state,
+        }
+    }
+
+    /// The name of the state the machine is in
+    pub fn name(&self) -> &str {
+        &self.definition.states[self.state]
+    }
+
+    /// The event called `event` of this machine's definition, carrying `data`, if there is one
+    pub fn event(&self, event: &str, data: D) -> Option<DynamicEvent<D>> {
+        let index = self.definition.events.iter().position(|e| *e == event)?;
+        self.event_by_id(
+            EventId {
+                owner: self.definition.owner,
+                index,
+            },
+            data,
+        )
+    }
+
+    /// The event with ID `event` of this machine's definition, carrying `data`, if there is one.
+    /// There isn't if the ID came from a different builder.
+    pub fn event_by_id(&self, event: EventId, data: D) -> Option<DynamicEvent<D>> {
+        if event.owner != self.definition.owner {
+            return None;
+        }
+        let name = self.definition.events.get(event.index)?;
+        Some(DynamicEvent {
+            id: event,
+            name: name.clone(),
+            data,
+        })
+    }
+
+    /// The names of the events the machine's current state has transitions for
+    pub fn available_events(&self) -> &[Name] {
+        &self.definition.accepted[self.state]
+    }
+
+    /// Returns true if the machine's current state has a transition for the event. The
+    /// transition's handler may of course still fail. Events made by machines from other builders
+    /// are never handled.
+    pub fn can_handle(&self, event: &DynamicEvent<D>) -> bool {
+        self.transition_for(event).is_some()
+    }
+
+    fn transition_for(&self, event: &DynamicEvent<D>) -> Option<&Target<C, E, D>> {
+        if event.id.owner != self.definition.owner {
+            return None;
+        }
+        self.definition.table[self.state]
+            .get(event.id.index)
+            .and_then(Option::as_ref)
+    }
+}
+
+impl<C, E: Error, D> StateMachine<Self, DynamicEvent<D>, C> for DynamicMachine<C, E, D> {
+    type Error = E;
+
+    fn on_event(self, event: DynamicEvent<D>) -> TransitionResult<Self, E, C> {
+        let target = match self.transition_for(&event) {
+            Some(target) => target.clone(),
+            None => {
+                return TransitionResult::InvalidTransition(InvalidTransition {
+                    machine: self.definition.name.clone(),
+                    state: self.definition.states[self.state].clone(),
+                    event: event.name,
+                    accepted: self.definition.accepted[self.state].clone(),
+                })
+            }
+        };
+        let commands = match &target.handler {
+            Some(handler) => match handler(event.data) {
+                Ok(commands) => commands,
+                Err(e) => return TransitionResult::Err(e),
+            },
+            None => Commands::default(),
+        };
+        TransitionResult::Ok {
+            commands,
+            new_state: Self {
+                state: target.to,
+                ..self
+            },
+        }
+    }
+
+    fn state(&self) -> &Self {
+        self
+    }
+}
+
+impl<C, E, D> Clone for DynamicMachine<C, E, D> {
+    fn clone(&self) -> Self {
+        Self {
+            definition: self.definition.clone(),
+            state: self.state,
+        }
+    }
+}
+
+impl<C, E, D> Debug for DynamicMachine<C, E, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicMachine")
+            .field("machine", &self.definition.name)
+            .field("state", &self.name())
+            .finish()
+    }
+}
+
+/// Displays the name of the state the machine is in
+impl<C, E, D> Display for DynamicMachine<C, E, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl<C, E, D> Named for DynamicMachine<C, E, D> {
+    fn name(&self) -> &Name {
+        &self.definition.states[self.state]
+    }
+}
+
+impl<C, E, D> DescribeMachine for DynamicMachine<C, E, D> {
+    fn machine_name(&self) -> &Name {
+        &self.definition.name
+    }
+
+    fn state_names(&self) -> &[Name] {
+        &self.definition.states
+    }
+
+    fn event_names(&self) -> &[Name] {
+        &self.definition.events
+    }
+
+    fn transitions(&self) -> &[TransitionInfo] {
+        &self.definition.transitions
+    }
+}
+
+/// An event of a [DynamicMachine], carrying data of type `D`. Events are made by the machine, with
+/// [DynamicMachine::event] or [DynamicMachine::event_by_id], and machines built by other builders
+/// treat them as invalid transitions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DynamicEvent<D = ()> {
+    id: EventId,
+    name: Name,
+    /// The data the event carries
+    pub data: D,
+}
+
+impl<D> DynamicEvent<D> {
+    /// The ID of the event
+    pub fn id(&self) -> EventId {
+        self.id
+    }
+
+    /// The name of the event
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<D> Named for DynamicEvent<D> {
+    fn name(&self) -> &Name {
+        &self.name
+    }
+}
+
+/// Displays the name of the event
+impl<D> Display for DynamicEvent<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// A mistake in the definition of a [DynamicMachine]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefinitionError {
+    /// The state has more than one transition for the event
+    DuplicateTransition { state: String, event: String },
+    /// The state ID was handed out by a different builder
+    UnknownState(StateId),
+    /// The machine was to start in a state which hasn't been added
+    UnknownInitialState(String),
+    /// The event ID was handed out by a different builder
+    UnknownEvent(EventId),
+}
+
+impl Display for DefinitionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateTransition { state, event } => write!(
+                f,
+                "State `{}` has more than one transition for event `{}`",
+                state, event
+            ),
+            Self::UnknownState(id) => write!(
+                f,
+                "State ID {} was handed out by a different builder",
+                id.index
+            ),
+            Self::UnknownInitialState(state) => write!(f, "Initial state `{}` is unknown", state),
+            Self::UnknownEvent(id) => write!(
+                f,
+                "Event ID {} was handed out by a different builder",
+                id.index
+            ),
+        }
+    }
+}
+
+impl Error for DefinitionError {}
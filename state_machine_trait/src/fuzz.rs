@@ -0,0 +1,53 @@
+//! Support for fuzzing state machines with [arbitrary]-based fuzzers like cargo-fuzz.
+//!
+//! Machines defined with `#[fsm(arbitrary)]` get an `Arbitrary` implementation for their events
+//! enum, so a fuzz target can turn the fuzzer's input into a stream of events with [fuzz_events]:
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| {
+//!     fuzz_events(CardReader::Locked(Locked {}), data);
+//! });
+//! ```
+
+pub use arbitrary;
+
+use crate::{StateMachine, TransitionResult};
+use arbitrary::{Arbitrary, Unstructured};
+use std::{
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Decodes as many events as possible from `data` and feeds them into the machine one at a time,
+/// returning the machine it ends up as.
+///
+/// Events the machine's current state has no transition for, or whose handlers return errors,
+/// are skipped, leaving the machine as it was (which is why it must be `Clone`). If a handler
+/// panics, the events leading up to the panic are printed to stderr before the panic is resumed,
+/// so that the fuzzer flags it as a crash. Events may borrow their data from `data`.
+pub fn fuzz_events<'a, M, E, C>(machine: M, data: &'a [u8]) -> M
+where
+    M: StateMachine<M, E, C> + Clone,
+    E: Arbitrary<'a> + Clone + Debug,
+{
+    let mut machine = machine;
+    let mut data = Unstructured::new(data);
+    let mut history = vec![];
+    while !data.is_empty() {
+        let event = match E::arbitrary(&mut data) {
+            Ok(event) => event,
+            Err(_) => break,
+        };
+        history.push(event.clone());
+        let unchanged = machine.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| machine.on_event(event)));
+        machine = match result {
+            Ok(TransitionResult::Ok { new_state, .. }) => new_state,
+            Ok(TransitionResult::InvalidTransition(_)) | Ok(TransitionResult::Err(_)) => unchanged,
+            Err(panic) => {
+                eprintln!("State machine handler panicked after events: {:?}", history);
+                panic::resume_unwind(panic)
+            }
+        };
+    }
+    machine
+}
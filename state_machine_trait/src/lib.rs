@@ -1,38 +1,199 @@
-use std::error::Error;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-/// This trait defines a state machine (more formally, a [finite state
-/// transducer](https://en.wikipedia.org/wiki/Finite-state_transducer)) which accepts events (the
-/// input alphabet), uses them to mutate itself, and (may) output some commands (the output
-/// alphabet) as a result.
-pub trait StateMachine<State, Event, Command> {
-    /// The error type produced by this state machine when handling events
-    type Error: Error;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-    /// Handle an incoming event
-    fn on_event(self, event: Event) -> TransitionResult<State, Self::Error, Command>;
+mod commands;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod dynamic;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod model_check;
+mod names;
+#[cfg(feature = "std")]
+pub mod observe;
+#[cfg(feature = "proptest")]
+pub mod proptest_support;
+#[cfg(feature = "std")]
+pub mod test_support;
+pub mod transaction;
 
-    /// Returns the current state of the machine
-    fn state(&self) -> &State;
+#[cfg(feature = "alloc")]
+pub use commands::Commands;
+#[doc(hidden)]
+pub use commands::{CollectionOf, CountingSink};
+pub use commands::{CommandBuffer, CommandCollection, CommandSink, FnSink};
+pub use names::{Name, NameList};
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
+
+use core::{
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    iter::FromIterator,
+};
+
+// The collection of commands is the last type parameter of `StateMachine` and
+// `TransitionResult`. It defaults to a `Vec` when there's an allocator, and has no default
+// otherwise, so that enabling `alloc` elsewhere in a build can't change what a machine without it
+// means. These macros declare each of them once for both cases.
+macro_rules! state_machine_trait {
+    ($($collection:tt)*) => {
+        /// This trait defines a state machine (more formally, a [finite state
+        /// transducer](https://en.wikipedia.org/wiki/Finite-state_transducer)) which accepts events
+        /// (the input alphabet), uses them to mutate itself, and (may) output some commands (the
+        /// output alphabet) as a result. The commands of each transition are collected into a
+        /// `Collection`, which is a [Commands] (a `Vec`) unless the machine chooses otherwise.
+        pub trait StateMachine<State, Event, Command, $($collection)*> {
+            /// The error type produced by this state machine when handling events
+            type Error: Error;
+
+            /// Handle an incoming event
+            fn on_event(
+                self,
+                event: Event,
+            ) -> TransitionResult<State, Self::Error, Command, Collection>;
+
+            /// Handle an incoming event, sending the commands it produces to `sink` rather than
+            /// returning them. Machines defined with `fsm!`'s `command_sink` option implement this
+            /// without collecting the commands first, so it needn't allocate. That also means their
+            /// handlers' commands reach the sink as they're emitted: if a handler emits some
+            /// commands and then fails, those commands stay in the sink even though the transition
+            /// didn't happen.
+            fn on_event_into(
+                self,
+                event: Event,
+                sink: &mut dyn CommandSink<Command>,
+            ) -> Result<State, TransitionError<Self::Error>>
+            where
+                Self: Sized,
+                Collection: IntoIterator<Item = Command>,
+            {
+                let (new_state, commands) = self.on_event(event).into_result()?;
+                for command in commands {
+                    sink.emit(command);
+                }
+                Ok(new_state)
+            }
+
+            /// Returns the current state of the machine
+            fn state(&self) -> &State;
+        }
+    };
 }
 
-// TODO: Likely need to return existing state with invalid trans/err
-pub enum TransitionResult<StateMachine, StateMachineError, StateMachineCommand> {
-    /// This state does not define a transition for this event
-    InvalidTransition,
-    /// The transition was successful
-    Ok {
-        commands: Vec<StateMachineCommand>,
-        new_state: StateMachine,
-    },
-    /// There an error performing the transition
-    Err(StateMachineError),
+#[cfg(feature = "alloc")]
+state_machine_trait!(Collection = Commands<Command>);
+#[cfg(not(feature = "alloc"))]
+state_machine_trait!(Collection);
+
+/// Static metadata describing the definition of a state machine, so that generic tools can work
+/// with any machine without needing to know its definition. Everything is listed in the order it
+/// first appears in the definition.
+pub trait MachineDefinition {
+    /// The name of the machine
+    const NAME: &'static Name;
+    /// The names of all the machine's states
+    const STATES: &'static [Name];
+    /// The names of all the events the machine accepts
+    const EVENTS: &'static [Name];
+    /// Every transition the machine can make. Transitions defined for several states, events, or
+    /// destinations have one entry for each combination of them.
+    const TRANSITIONS: &'static [TransitionInfo];
+}
+
+/// The same metadata as [MachineDefinition], but read from an instance of the machine, so that it
+/// can also describe machines whose definition is only known at runtime, like
+/// [DynamicMachine](crate::dynamic::DynamicMachine). Every [MachineDefinition] implements it.
+pub trait DescribeMachine {
+    /// The name of the machine
+    fn machine_name(&self) -> &Name;
+    /// The names of all the machine's states
+    fn state_names(&self) -> &[Name];
+    /// The names of all the events the machine accepts
+    fn event_names(&self) -> &[Name];
+    /// Every transition the machine can make
+    fn transitions(&self) -> &[TransitionInfo];
 }
 
-impl<S, E, C> TransitionResult<S, E, C> {
+impl<M: MachineDefinition> DescribeMachine for M {
+    fn machine_name(&self) -> &Name {
+        M::NAME
+    }
+
+    fn state_names(&self) -> &[Name] {
+        M::STATES
+    }
+
+    fn event_names(&self) -> &[Name] {
+        M::EVENTS
+    }
+
+    fn transitions(&self) -> &[TransitionInfo] {
+        M::TRANSITIONS
+    }
+}
+
+/// Implemented by a machine and its events, giving the name of the state the machine is in, or of
+/// the event, as listed in its [MachineDefinition] (or [DescribeMachine])
+pub trait Named {
+    /// The name of the state or event
+    fn name(&self) -> &Name;
+}
+
+/// Describes one transition of a state machine, as listed by [MachineDefinition::TRANSITIONS]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TransitionInfo {
+    /// The state the transition starts from
+    pub from: Name,
+    /// The event which causes the transition
+    pub event: Name,
+    /// The state the transition ends in. For internal transitions this is the same as `from`.
+    pub to: Name,
+    /// The name of the handler called for the transition, if it has one
+    pub handler: Option<&'static str>,
+    /// True if the transition is internal, meaning the state data is updated in place rather than
+    /// the machine moving to a new state
+    pub internal: bool,
+    /// The doc comments written before the transition, if it has any, one line of text each
+    pub doc: Option<&'static str>,
+}
+
+macro_rules! transition_result {
+    ($($collection:tt)*) => {
+        // TODO: Likely need to return existing state with invalid trans/err
+        pub enum TransitionResult<StateMachine, StateMachineError, StateMachineCommand, $($collection)*> {
+            /// This state does not define a transition for this event
+            InvalidTransition(InvalidTransition),
+            /// The transition was successful
+            Ok {
+                commands: <Collection as CollectionOf<StateMachineCommand>>::Collection,
+                new_state: StateMachine,
+            },
+            /// There an error performing the transition
+            Err(StateMachineError),
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+transition_result!(Collection = Commands<StateMachineCommand>);
+#[cfg(not(feature = "alloc"))]
+transition_result!(Collection);
+
+impl<S, E, C, CC> TransitionResult<S, E, C, CC> {
     pub fn ok<CI, IS>(commands: CI, new_state: IS) -> Self
     where
         CI: IntoIterator<Item = C>,
         IS: Into<S>,
+        CC: FromIterator<C>,
     {
         Self::Ok {
             commands: commands.into_iter().collect(),
@@ -40,24 +201,223 @@ impl<S, E, C> TransitionResult<S, E, C> {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn default<IS>() -> Self
     where
         IS: Into<S> + Default,
+        CC: Default,
     {
         Self::Ok {
-            commands: vec![],
+            commands: CC::default(),
             new_state: IS::default().into(),
         }
     }
 
-    // TODO: Make test only or something?
-    pub fn unwrap(self) -> (S, Vec<C>) {
+    /// True if the transition was successful
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok { .. })
+    }
+
+    /// True if the state did not define a transition for the event
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, Self::InvalidTransition(_))
+    }
+
+    /// True if there was an error performing the transition
+    pub fn is_err(&self) -> bool {
+        matches!(self, Self::Err(_))
+    }
+
+    /// Converts the new state of a successful transition with `f`
+    pub fn map_state<S2, F: FnOnce(S) -> S2>(self, f: F) -> TransitionResult<S2, E, C, CC> {
+        match self {
+            Self::Ok {
+                commands,
+                new_state,
+            } => TransitionResult::Ok {
+                commands,
+                new_state: f(new_state),
+            },
+            Self::InvalidTransition(i) => TransitionResult::InvalidTransition(i),
+            Self::Err(e) => TransitionResult::Err(e),
+        }
+    }
+
+    /// Converts each of the commands produced by a successful transition with `f`
+    pub fn map_commands<C2, F: FnMut(C) -> C2>(self, f: F) -> TransitionResult<S, E, C2, CC::Of<C2>>
+    where
+        CC: CommandCollection<C>,
+    {
+        match self {
+            Self::Ok {
+                commands,
+                new_state,
+            } => TransitionResult::Ok {
+                commands: commands.into_iter().map(f).collect(),
+                new_state,
+            },
+            Self::InvalidTransition(i) => TransitionResult::InvalidTransition(i),
+            Self::Err(e) => TransitionResult::Err(e),
+        }
+    }
+
+    /// Converts the error of a failed transition with `f`
+    pub fn map_err<E2, F: FnOnce(E) -> E2>(self, f: F) -> TransitionResult<S, E2, C, CC> {
+        match self {
+            Self::Ok {
+                commands,
+                new_state,
+            } => TransitionResult::Ok {
+                commands,
+                new_state,
+            },
+            Self::InvalidTransition(i) => TransitionResult::InvalidTransition(i),
+            Self::Err(e) => TransitionResult::Err(f(e)),
+        }
+    }
+
+    /// If the transition was successful, makes another from its new state with `f`, such as by
+    /// handing the machine another event. The commands of both transitions are kept, in order.
+    pub fn and_then<S2, F>(self, f: F) -> TransitionResult<S2, E, C, CC>
+    where
+        F: FnOnce(S) -> TransitionResult<S2, E, C, CC>,
+        CC: CommandCollection<C>,
+    {
+        match self {
+            Self::Ok {
+                mut commands,
+                new_state,
+            } => match f(new_state) {
+                TransitionResult::Ok {
+                    commands: more,
+                    new_state,
+                } => {
+                    commands.extend(more);
+                    TransitionResult::Ok {
+                        commands,
+                        new_state,
+                    }
+                }
+                other => other,
+            },
+            Self::InvalidTransition(i) => TransitionResult::InvalidTransition(i),
+            Self::Err(e) => TransitionResult::Err(e),
+        }
+    }
+
+    /// Converts into a [Result], holding the new state and commands if the transition was
+    /// successful
+    pub fn into_result(self) -> Result<(S, CC), TransitionError<E>> {
+        match self {
+            Self::Ok {
+                commands,
+                new_state,
+            } => Ok((new_state, commands)),
+            Self::InvalidTransition(i) => Err(TransitionError::InvalidTransition(i)),
+            Self::Err(e) => Err(TransitionError::Handler(e)),
+        }
+    }
+
+    /// Returns the new state and commands of a successful transition, panicking with `msg` (and
+    /// what went wrong) otherwise
+    pub fn expect(self, msg: &str) -> (S, CC)
+    where
+        E: Debug,
+    {
+        match self.into_result() {
+            Ok(ok) => ok,
+            Err(e) => panic!("{}: {:?}", msg, e),
+        }
+    }
+
+    /// Returns the new state and commands of a successful transition, panicking otherwise
+    pub fn unwrap(self) -> (S, CC) {
         match self {
             Self::Ok {
                 commands,
                 new_state,
             } => (new_state, commands),
-            _ => panic!("Transition was not successful!"),
+            Self::InvalidTransition(i) => panic!("Transition was not successful: {}", i),
+            Self::Err(_) => panic!("Transition was not successful: the handler returned an error"),
         }
     }
 }
+
+/// Lets handlers return a `Result` of a transition, so they can use `?` on fallible calls. An
+/// error becomes [TransitionResult::Err].
+impl<S, E, C, CC> From<Result<TransitionResult<S, E, C, CC>, E>> for TransitionResult<S, E, C, CC> {
+    fn from(result: Result<TransitionResult<S, E, C, CC>, E>) -> Self {
+        match result {
+            Ok(transition) => transition,
+            Err(e) => Self::Err(e),
+        }
+    }
+}
+
+impl<S, E, C, CC> From<TransitionResult<S, E, C, CC>> for Result<(S, CC), TransitionError<E>> {
+    fn from(transition: TransitionResult<S, E, C, CC>) -> Self {
+        transition.into_result()
+    }
+}
+
+/// Why a transition was not successful
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransitionError<E> {
+    /// The state did not define a transition for the event
+    InvalidTransition(InvalidTransition),
+    /// The transition's handler returned an error
+    Handler(E),
+}
+
+impl<E: Display> Display for TransitionError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTransition(i) => Display::fmt(i, f),
+            Self::Handler(e) => write!(f, "Transition failed: {}", e),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for TransitionError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidTransition(i) => Some(i),
+            Self::Handler(e) => Some(e),
+        }
+    }
+}
+
+/// Describes an event sent to a machine in a state which has no transition for it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InvalidTransition {
+    /// The name of the machine
+    pub machine: Name,
+    /// The state the machine was in
+    pub state: Name,
+    /// The kind of event the machine was sent
+    pub event: Name,
+    /// The kinds of events the state does have transitions for
+    pub accepted: NameList,
+}
+
+impl Display for InvalidTransition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} cannot handle event `{}` in state `{}`",
+            self.machine, self.event, self.state
+        )?;
+        match self.accepted.split_first() {
+            None => write!(f, ", which accepts no events"),
+            Some((first, rest)) => {
+                write!(f, ", which accepts `{}`", first)?;
+                for event in rest {
+                    write!(f, ", `{}`", event)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for InvalidTransition {}
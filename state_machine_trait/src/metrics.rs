@@ -0,0 +1,250 @@
+//! Counting the transitions machines make, for export to whatever metrics system is in use.
+//!
+//! Implement [MetricsRecorder] to forward measurements elsewhere, or use [InMemoryMetrics] to keep
+//! them around for querying. Either way, wrap the recorder in a [MetricsObserver] and hand events
+//! to machines through
+//! [ObserveTransitions::on_event_observed](crate::observe::ObserveTransitions::on_event_observed).
+
+use crate::{
+    observe::{ObservedTransition, TransitionObserver, TransitionOutcome},
+    Name,
+};
+use std::{collections::HashMap, time::Duration};
+
+/// Receives measurements about the transitions machines make. Every method has an empty default
+/// implementation, so recorders only need to implement the ones they care about.
+pub trait MetricsRecorder {
+    /// `machine` went from `from` to `to` on `event`
+    fn record_transition(&mut self, machine: &Name, from: &Name, event: &Name, to: &Name) {
+        let _ = (machine, from, event, to);
+    }
+
+    /// `machine` was sent `event` while in `state`, which has no transition for it
+    fn record_invalid(&mut self, machine: &Name, state: &Name, event: &Name) {
+        let _ = (machine, state, event);
+    }
+
+    /// The handler for `event` in `state` returned an error
+    fn record_error(&mut self, machine: &Name, state: &Name, event: &Name) {
+        let _ = (machine, state, event);
+    }
+
+    /// `machine` took `elapsed` to handle `event` while in `from`, whatever the outcome
+    fn record_latency(&mut self, machine: &Name, from: &Name, event: &Name, elapsed: Duration) {
+        let _ = (machine, from, event, elapsed);
+    }
+}
+
+impl<R: MetricsRecorder + ?Sized> MetricsRecorder for &mut R {
+    fn record_transition(&mut self, machine: &Name, from: &Name, event: &Name, to: &Name) {
+        (**self).record_transition(machine, from, event, to)
+    }
+
+    fn record_invalid(&mut self, machine: &Name, state: &Name, event: &Name) {
+        (**self).record_invalid(machine, state, event)
+    }
+
+    fn record_error(&mut self, machine: &Name, state: &Name, event: &Name) {
+        (**self).record_error(machine, state, event)
+    }
+
+    fn record_latency(&mut self, machine: &Name, from: &Name, event: &Name, elapsed: Duration) {
+        (**self).record_latency(machine, from, event, elapsed)
+    }
+}
+
+/// Feeds the transitions it observes to a [MetricsRecorder]
+#[derive(Debug, Clone, Default)]
+pub struct MetricsObserver<R> {
+    recorder: R,
+}
+
+impl<R: MetricsRecorder> MetricsObserver<R> {
+    /// Creates an observer which reports to `recorder`
+    pub fn new(recorder: R) -> Self {
+        Self { recorder }
+    }
+
+    /// The recorder being reported to
+    pub fn recorder(&self) -> &R {
+        &self.recorder
+    }
+
+    /// Returns the recorder, consuming the observer
+    pub fn into_inner(self) -> R {
+        self.recorder
+    }
+}
+
+impl<R: MetricsRecorder> TransitionObserver for MetricsObserver<R> {
+    fn on_transition(&mut self, t: &ObservedTransition) {
+        match &t.outcome {
+            TransitionOutcome::Ok { to, .. } => self
+                .recorder
+                .record_transition(&t.machine, &t.from, &t.event, to),
+            TransitionOutcome::InvalidTransition => {
+                self.recorder.record_invalid(&t.machine, &t.from, &t.event)
+            }
+            TransitionOutcome::Err => self.recorder.record_error(&t.machine, &t.from, &t.event),
+        }
+        self.recorder
+            .record_latency(&t.machine, &t.from, &t.event, t.elapsed);
+    }
+}
+
+/// The upper bounds of the buckets used by [LatencyHistogram], in microseconds. Anything slower
+/// than the last bound lands in a final, unbounded bucket.
+pub const LATENCY_BUCKETS_MICROS: &[u64] = &[1, 10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// A histogram of how long handling an event took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS_MICROS.len() + 1],
+    sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; LATENCY_BUCKETS_MICROS.len() + 1],
+            sum: Duration::default(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Adds one measurement to the histogram
+    pub fn record(&mut self, elapsed: Duration) {
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|bound| elapsed <= Duration::from_micros(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        self.counts[bucket] += 1;
+        self.sum += elapsed;
+    }
+
+    /// The number of measurements recorded
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The total of all measurements recorded
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Each bucket's upper bound (`None` for the unbounded last bucket) and the number of
+    /// measurements which fell in it, and not in any earlier bucket
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKETS_MICROS
+            .iter()
+            .map(|bound| Some(Duration::from_micros(*bound)))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+}
+
+/// A [MetricsRecorder] which keeps everything in memory, for querying from tests or periodic
+/// export
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMetrics {
+    transitions: HashMap<(Name, Name, Name, Name), u64>,
+    invalid: HashMap<(Name, Name, Name), u64>,
+    errors: HashMap<(Name, Name, Name), u64>,
+    latency: HashMap<(Name, Name, Name), LatencyHistogram>,
+}
+
+impl InMemoryMetrics {
+    /// Creates an empty set of metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of times `machine` went from `from` to `to` on `event`
+    pub fn transition_count(&self, machine: &str, from: &str, event: &str, to: &str) -> u64 {
+        self.transitions()
+            .find(|(key, _)| *key == (machine, from, event, to))
+            .map_or(0, |(_, count)| count)
+    }
+
+    /// The number of times `machine` was sent `event` in `state`, which has no transition for it
+    pub fn invalid_count(&self, machine: &str, state: &str, event: &str) -> u64 {
+        self.invalid_transitions()
+            .find(|(key, _)| *key == (machine, state, event))
+            .map_or(0, |(_, count)| count)
+    }
+
+    /// The number of times the handler for `event` in `state` returned an error
+    pub fn error_count(&self, machine: &str, state: &str, event: &str) -> u64 {
+        self.errors()
+            .find(|(key, _)| *key == (machine, state, event))
+            .map_or(0, |(_, count)| count)
+    }
+
+    /// How long `machine` took to handle `event` in `from`, if it ever has
+    pub fn latency<'a>(
+        &'a self,
+        machine: &'a str,
+        from: &'a str,
+        event: &'a str,
+    ) -> Option<&'a LatencyHistogram> {
+        self.latency
+            .iter()
+            .find(|((m, f, e), _)| m == machine && f == from && e == event)
+            .map(|(_, histogram)| histogram)
+    }
+
+    /// Every `(machine, from, event, to)` transition taken, with the number of times it was
+    /// taken, in no particular order
+    pub fn transitions(&self) -> impl Iterator<Item = ((&str, &str, &str, &str), u64)> + '_ {
+        self.transitions
+            .iter()
+            .map(|((m, f, e, t), v)| ((m.as_str(), f.as_str(), e.as_str(), t.as_str()), *v))
+    }
+
+    /// Every `(machine, state, event)` with an invalid transition, with the number of times it
+    /// happened, in no particular order
+    pub fn invalid_transitions(&self) -> impl Iterator<Item = ((&str, &str, &str), u64)> + '_ {
+        self.invalid
+            .iter()
+            .map(|((m, s, e), v)| ((m.as_str(), s.as_str(), e.as_str()), *v))
+    }
+
+    /// Every `(machine, state, event)` whose handler returned an error, with the number of times
+    /// it did, in no particular order
+    pub fn errors(&self) -> impl Iterator<Item = ((&str, &str, &str), u64)> + '_ {
+        self.errors
+            .iter()
+            .map(|((m, s, e), v)| ((m.as_str(), s.as_str(), e.as_str()), *v))
+    }
+}
+
+impl MetricsRecorder for InMemoryMetrics {
+    fn record_transition(&mut self, machine: &Name, from: &Name, event: &Name, to: &Name) {
+        *self
+            .transitions
+            .entry((machine.clone(), from.clone(), event.clone(), to.clone()))
+            .or_default() += 1;
+    }
+
+    fn record_invalid(&mut self, machine: &Name, state: &Name, event: &Name) {
+        *self
+            .invalid
+            .entry((machine.clone(), state.clone(), event.clone()))
+            .or_default() += 1;
+    }
+
+    fn record_error(&mut self, machine: &Name, state: &Name, event: &Name) {
+        *self
+            .errors
+            .entry((machine.clone(), state.clone(), event.clone()))
+            .or_default() += 1;
+    }
+
+    fn record_latency(&mut self, machine: &Name, from: &Name, event: &Name, elapsed: Duration) {
+        self.latency
+            .entry((machine.clone(), from.clone(), event.clone()))
+            .or_default()
+            .record(elapsed);
+    }
+}
@@ -0,0 +1,253 @@
+//! Bounded exhaustive model checking of state machines.
+//!
+//! Unlike random testing, the [ModelChecker] explores *every* state reachable from an initial
+//! state within a given number of events, so any invariant violation or handler error within
+//! that bound is guaranteed to be found. Exploration is breadth first, so the counterexamples
+//! found are as short as possible.
+
+use crate::{StateMachine, TransitionResult};
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::{self, Debug, Display, Formatter},
+    hash::Hash,
+};
+
+type EventGenerator<M, E> = Box<dyn Fn(&M) -> Vec<E>>;
+type Invariant<M, C> = Box<dyn Fn(&M, &[C]) -> Result<(), String>>;
+
+/// Explores all the states of a machine reachable within some number of events, checking
+/// invariants along the way.
+///
+/// States are deduplicated, so each distinct state is only explored once, which requires the
+/// machine to implement `Hash` and `Eq`.
+pub struct ModelChecker<M, E, C> {
+    initial: M,
+    max_depth: usize,
+    events: EventGenerator<M, E>,
+    invariants: Vec<(String, Invariant<M, C>)>,
+}
+
+impl<M, E, C> ModelChecker<M, E, C>
+where
+    M: StateMachine<M, E, C> + Clone + Hash + Eq,
+    E: Clone,
+{
+    /// Creates a checker starting from `initial`. `events` is called for every state explored,
+    /// and should return every event (with whatever representative data) to try in that state.
+    pub fn new(initial: M, events: impl Fn(&M) -> Vec<E> + 'static) -> Self {
+        Self {
+            initial,
+            max_depth: 10,
+            events: Box::new(events),
+            invariants: vec![],
+        }
+    }
+
+    /// Sets the maximum number of events in a sequence to explore. Defaults to 10.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Adds an invariant, which is checked against the initial state and after every transition,
+    /// where it is also given the commands produced by the transition
+    pub fn invariant(
+        mut self,
+        name: impl Into<String>,
+        invariant: impl Fn(&M, &[C]) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.invariants.push((name.into(), Box::new(invariant)));
+        self
+    }
+
+    /// Explores the machine, returning everything which went wrong
+    pub fn run(&self) -> CheckReport<E> {
+        // Each explored transition is stored as the index of the transition leading to the state
+        // it started from, plus its event, so traces can be rebuilt without storing one per state
+        let mut path: Vec<(Option<usize>, E)> = vec![];
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut report = CheckReport {
+            states_explored: 0,
+            complete: true,
+            counterexamples: vec![],
+        };
+
+        let trace = |path: &Vec<(Option<usize>, E)>, mut at: Option<usize>| {
+            let mut events = vec![];
+            while let Some(i) = at {
+                events.push(path[i].1.clone());
+                at = path[i].0;
+            }
+            events.reverse();
+            events
+        };
+
+        report.check_invariants(&self.invariants, &self.initial, &[], Vec::new);
+        visited.insert(self.initial.clone());
+        queue.push_back((self.initial.clone(), None, 0));
+
+        while let Some((machine, reached_by, depth)) = queue.pop_front() {
+            report.states_explored += 1;
+            let events = (self.events)(&machine);
+            if depth == self.max_depth {
+                // The search is only complete if nothing new could be found from here: no new
+                // states, and no failures on the way to states already seen
+                let unexplored = events
+                    .into_iter()
+                    .any(|e| match machine.clone().on_event(e) {
+                        TransitionResult::Ok {
+                            commands,
+                            new_state,
+                        } => {
+                            !visited.contains(&new_state)
+                                || self
+                                    .invariants
+                                    .iter()
+                                    .any(|(_, invariant)| invariant(&new_state, &commands).is_err())
+                        }
+                        TransitionResult::InvalidTransition(_) => false,
+                        TransitionResult::Err(_) => true,
+                    });
+                if unexplored {
+                    report.complete = false;
+                }
+                continue;
+            }
+            for event in events {
+                let step = path.len();
+                path.push((reached_by, event.clone()));
+                match machine.clone().on_event(event) {
+                    TransitionResult::Ok {
+                        commands,
+                        new_state,
+                    } => {
+                        let found = report.check_invariants(
+                            &self.invariants,
+                            &new_state,
+                            &commands,
+                            || trace(&path, Some(step)),
+                        );
+                        // No point exploring beyond a state which is already broken
+                        if !found && visited.insert(new_state.clone()) {
+                            queue.push_back((new_state, Some(step), depth + 1));
+                        }
+                    }
+                    TransitionResult::InvalidTransition(_) => {}
+                    TransitionResult::Err(e) => {
+                        report.counterexamples.push(Counterexample {
+                            events: trace(&path, Some(step)),
+                            violation: Violation::HandlerError(e.to_string()),
+                        });
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+/// The outcome of a [ModelChecker] run
+#[derive(Debug, Clone)]
+pub struct CheckReport<E> {
+    /// How many distinct states were explored
+    pub states_explored: usize,
+    /// True if every reachable state was explored, meaning there are no more transitions to be
+    /// found beyond the depth bound. A transition out of the last explored states which fails, or
+    /// violates an invariant, also makes the search incomplete, since it would be a counterexample
+    /// one event past the bound.
+    pub complete: bool,
+    /// Every problem found, shortest first
+    pub counterexamples: Vec<Counterexample<E>>,
+}
+
+impl<E> CheckReport<E> {
+    /// True if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.counterexamples.is_empty()
+    }
+
+    /// Checks all the invariants, recording any violations with the trace returned by `trace`.
+    /// Returns true if any were violated.
+    fn check_invariants<M, C>(
+        &mut self,
+        invariants: &[(String, Invariant<M, C>)],
+        machine: &M,
+        commands: &[C],
+        trace: impl Fn() -> Vec<E>,
+    ) -> bool {
+        let mut found = false;
+        for (name, invariant) in invariants {
+            if let Err(reason) = invariant(machine, commands) {
+                self.counterexamples.push(Counterexample {
+                    events: trace(),
+                    violation: Violation::Invariant {
+                        name: name.clone(),
+                        reason,
+                    },
+                });
+                found = true;
+            }
+        }
+        found
+    }
+}
+
+impl<E: Debug> Display for CheckReport<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Explored {} states ({})",
+            self.states_explored,
+            if self.complete {
+                "complete"
+            } else {
+                "stopped at depth bound"
+            }
+        )?;
+        for c in &self.counterexamples {
+            writeln!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+/// A sequence of events, starting from the initial state, which leads to a problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample<E> {
+    /// The events which lead to the problem
+    pub events: Vec<E>,
+    /// What went wrong
+    pub violation: Violation,
+}
+
+impl<E: Debug> Display for Counterexample<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after events {:?}", self.violation, self.events)
+    }
+}
+
+/// A problem found by a [ModelChecker]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// An invariant did not hold
+    Invariant {
+        /// The name the invariant was added with
+        name: String,
+        /// The reason the invariant gave for failing
+        reason: String,
+    },
+    /// A handler returned an error
+    HandlerError(String),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Invariant { name, reason } => {
+                write!(f, "Invariant `{}` violated: {}", name, reason)
+            }
+            Violation::HandlerError(e) => write!(f, "Handler failed: {}", e),
+        }
+    }
+}
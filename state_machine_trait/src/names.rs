@@ -0,0 +1,219 @@
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::{self, Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+#[cfg(feature = "std")]
+use std::{iter::FromIterator, string::String, sync::Arc, vec::Vec};
+
+/// The name of a machine, state or event. Machines defined with `fsm!` name everything with
+/// `&'static str`s, which [Name::new] wraps for free. Machines defined at runtime, like
+/// [DynamicMachine](crate::dynamic::DynamicMachine), share their names between everything which
+/// mentions them instead, so they needn't be leaked to live long enough. Either way, a name
+/// dereferences to a `str`, and compares, hashes and displays as one.
+#[derive(Clone)]
+pub struct Name(NameRepr);
+
+#[derive(Clone)]
+enum NameRepr {
+    Static(&'static str),
+    #[cfg(feature = "std")]
+    Shared(Arc<str>),
+}
+
+impl Name {
+    /// A name for a `&'static str`, usable in constants
+    pub const fn new(name: &'static str) -> Self {
+        Self(NameRepr::Static(name))
+    }
+
+    /// The name as a `str`
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            NameRepr::Static(name) => name,
+            #[cfg(feature = "std")]
+            NameRepr::Shared(name) => name,
+        }
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&'static str> for Name {
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Arc<str>> for Name {
+    fn from(name: Arc<str>) -> Self {
+        Self(NameRepr::Shared(name))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self(NameRepr::Shared(name.into()))
+    }
+}
+
+impl Debug for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Name {}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<Name> for str {
+    fn eq(&self, other: &Name) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Name> for &str {
+    fn eq(&self, other: &Name) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+/// A list of [Name]s, like the events a state accepts. As with a [Name], it's either a
+/// `&'static` slice, for machines defined with `fsm!`, or shared by everything mentioning it.
+/// It dereferences to a slice of names.
+#[derive(Clone)]
+pub struct NameList(NameListRepr);
+
+#[derive(Clone)]
+enum NameListRepr {
+    Static(&'static [Name]),
+    #[cfg(feature = "std")]
+    Shared(Arc<[Name]>),
+}
+
+impl NameList {
+    /// A list of the names in a `&'static` slice, usable in constants
+    pub const fn new(names: &'static [Name]) -> Self {
+        Self(NameListRepr::Static(names))
+    }
+
+    /// The names as a slice
+    pub fn as_slice(&self) -> &[Name] {
+        match &self.0 {
+            NameListRepr::Static(names) => names,
+            #[cfg(feature = "std")]
+            NameListRepr::Shared(names) => names,
+        }
+    }
+}
+
+impl Deref for NameList {
+    type Target = [Name];
+
+    fn deref(&self) -> &[Name] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Vec<Name>> for NameList {
+    fn from(names: Vec<Name>) -> Self {
+        Self(NameListRepr::Shared(names.into()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromIterator<Name> for NameList {
+    fn from_iter<I: IntoIterator<Item = Name>>(names: I) -> Self {
+        Self(NameListRepr::Shared(names.into_iter().collect()))
+    }
+}
+
+impl Debug for NameList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl PartialEq for NameList {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for NameList {}
+
+impl<const N: usize> PartialEq<[&str; N]> for NameList {
+    fn eq(&self, other: &[&str; N]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl Hash for NameList {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
@@ -0,0 +1,96 @@
+//! Hooks for watching the transitions a machine makes, without changing the machine itself.
+//!
+//! Any machine implementing [DescribeMachine] and [Named] (as machines defined with `fsm!` do)
+//! can be driven with [ObserveTransitions::on_event_observed], which reports every event handled
+//! to a [TransitionObserver].
+
+use crate::{DescribeMachine, Name, Named, StateMachine, TransitionResult};
+use std::time::{Duration, Instant};
+
+/// Something which wants to know about the transitions a machine makes
+pub trait TransitionObserver {
+    /// Called after the machine has handled an event
+    fn on_transition(&mut self, transition: &ObservedTransition);
+}
+
+impl<O: TransitionObserver + ?Sized> TransitionObserver for &mut O {
+    fn on_transition(&mut self, transition: &ObservedTransition) {
+        (**self).on_transition(transition)
+    }
+}
+
+/// Describes an event a machine handled, and what came of it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ObservedTransition {
+    /// The name of the machine
+    pub machine: Name,
+    /// The state the machine was in
+    pub from: Name,
+    /// The event the machine handled
+    pub event: Name,
+    /// What happened as a result
+    pub outcome: TransitionOutcome,
+    /// How long the machine took to handle the event, including the handler
+    pub elapsed: Duration,
+}
+
+/// What happened when a machine handled an event
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TransitionOutcome {
+    /// The machine moved to (or, for internal transitions, stayed in) the state `to`, producing
+    /// `commands` commands
+    Ok { to: Name, commands: usize },
+    /// The state the machine was in has no transition for the event
+    InvalidTransition,
+    /// The transition's handler returned an error
+    Err,
+}
+
+/// Extends machines with a way to handle events while reporting them to an observer
+pub trait ObserveTransitions<E, C>:
+    StateMachine<Self, E, C> + DescribeMachine + Named + Sized
+where
+    E: Named,
+{
+    /// Handles an event exactly like [StateMachine::on_event], also reporting the transition to
+    /// `observer`
+    fn on_event_observed<O: TransitionObserver>(
+        self,
+        event: E,
+        mut observer: O,
+    ) -> TransitionResult<Self, Self::Error, C> {
+        // The machine is consumed handling the event, so keep its names first
+        let machine = self.machine_name().clone();
+        let from = self.name().clone();
+        let event_name = event.name().clone();
+        let started = Instant::now();
+        let result = self.on_event(event);
+        let elapsed = started.elapsed();
+        let outcome = match &result {
+            TransitionResult::Ok {
+                commands,
+                new_state,
+            } => TransitionOutcome::Ok {
+                to: new_state.name().clone(),
+                commands: commands.len(),
+            },
+            TransitionResult::InvalidTransition(_) => TransitionOutcome::InvalidTransition,
+            TransitionResult::Err(_) => TransitionOutcome::Err,
+        };
+        observer.on_transition(&ObservedTransition {
+            machine,
+            from,
+            event: event_name,
+            outcome,
+            elapsed,
+        });
+        result
+    }
+}
+
+impl<M, E, C> ObserveTransitions<E, C> for M
+where
+    M: StateMachine<M, E, C> + DescribeMachine + Named,
+    E: Named,
+{
+}
@@ -0,0 +1,60 @@
+//! Support for property testing state machines with [proptest].
+//!
+//! Machines defined with `#[fsm(proptest)]` get an `Arbitrary` implementation for their events
+//! enum, which lets [check_event_sequences] drive random sequences of events through them.
+
+pub use proptest;
+
+use crate::{StateMachine, TransitionResult};
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    test_runner::{Config, TestCaseError, TestError, TestRunner},
+};
+use std::fmt::Debug;
+
+/// Runs random sequences of up to `max_len` events through machines created by `new_machine`,
+/// calling `invariant` with the machine and the commands it produced after every step. Sequences
+/// which make the invariant fail (or a handler return an error) are shrunk to a minimal failing
+/// sequence, which is returned inside the error.
+///
+/// Events the machine's current state has no transition for are skipped, leaving the machine
+/// as it was, which is why the machine must be `Clone`.
+pub fn check_event_sequences<M, E, C, F, I>(
+    config: Config,
+    max_len: usize,
+    new_machine: F,
+    invariant: I,
+) -> Result<(), TestError<Vec<E>>>
+where
+    M: StateMachine<M, E, C> + Clone,
+    E: Arbitrary + Clone + Debug,
+    F: Fn() -> M,
+    I: Fn(&M, &[C]) -> Result<(), String>,
+{
+    let mut runner = TestRunner::new(config);
+    runner.run(&vec(any::<E>(), 0..=max_len), |events| {
+        let mut machine = new_machine();
+        for (step, event) in events.into_iter().enumerate() {
+            let unchanged = machine.clone();
+            let (new_machine, commands) = match machine.on_event(event) {
+                TransitionResult::Ok {
+                    commands,
+                    new_state,
+                } => (new_state, commands),
+                TransitionResult::InvalidTransition(_) => (unchanged, vec![]),
+                TransitionResult::Err(e) => {
+                    return Err(TestCaseError::fail(format!(
+                        "Handler failed at step {}: {}",
+                        step, e
+                    )))
+                }
+            };
+            machine = new_machine;
+            invariant(&machine, &commands).map_err(|reason| {
+                TestCaseError::fail(format!("Invariant failed at step {}: {}", step, reason))
+            })?;
+        }
+        Ok(())
+    })
+}
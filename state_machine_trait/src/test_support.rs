@@ -0,0 +1,111 @@
+//! Helpers for testing state machines
+
+use crate::{StateMachine, TransitionResult};
+use std::fmt::{Debug, Write};
+
+/// Runs a machine through a sequence of events, asserting the state it ends up in and the
+/// commands it produces after each one, and evaluates to the final machine.
+///
+/// Each step is written as `event => expected_state_pattern, [expected commands]`, and steps are
+/// separated by `;`. On failure, the step, the machine's actual state, and the difference between
+/// the expected and actual commands are printed. The machine must implement `Debug`, as must
+/// the commands, which must also implement `PartialEq`.
+///
+/// ```ignore
+/// let cr = assert_trace!(CardReader::Locked(Locked {});
+///     CardReaderEvents::CardReadable("badguy".to_string()) => CardReader::ReadingCard(_),
+///         [Commands::ProcessData("badguy".to_string()), Commands::StartBlinkingLight];
+///     CardReaderEvents::CardRejected => CardReader::Locked(_), [Commands::StopBlinkingLight];
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_trace {
+    ($machine:expr; $($event:expr => $state:pat, [$($command:expr),* $(,)?]);* $(;)?) => {{
+        let machine = $machine;
+        let step = 0;
+        $(
+            let step = step + 1;
+            let machine = $crate::test_support::trace_step(
+                machine,
+                step,
+                stringify!($event),
+                $event,
+                |m| ::core::matches!(m, $state),
+                stringify!($state),
+                vec![$($command),*],
+            );
+        )*
+        let _ = step;
+        machine
+    }};
+}
+
+/// Performs a single step of [assert_trace], panicking with a description of what went wrong if
+/// the step doesn't go as expected. Not meant to be used directly.
+#[doc(hidden)]
+pub fn trace_step<M, E, C>(
+    machine: M,
+    step: usize,
+    event_str: &str,
+    event: E,
+    state_matches: impl Fn(&M) -> bool,
+    state_str: &str,
+    expected_commands: Vec<C>,
+) -> M
+where
+    M: StateMachine<M, E, C> + Debug,
+    C: Debug + PartialEq,
+{
+    let before = format!("{:?}", machine);
+    let (machine, commands) = match machine.on_event(event) {
+        TransitionResult::Ok {
+            commands,
+            new_state,
+        } => (new_state, commands),
+        TransitionResult::InvalidTransition(invalid) => panic!(
+            "Step {} (`{}`): invalid transition from state {}: {}",
+            step, event_str, before, invalid
+        ),
+        TransitionResult::Err(e) => panic!(
+            "Step {} (`{}`): transition from state {} failed: {}",
+            step, event_str, before, e
+        ),
+    };
+    if !state_matches(&machine) {
+        panic!(
+            "Step {} (`{}`): expected state `{}`, but the machine is in {:?}",
+            step, event_str, state_str, machine
+        );
+    }
+    if commands != expected_commands {
+        panic!(
+            "Step {} (`{}`): commands did not match (- expected, + actual):\n{}",
+            step,
+            event_str,
+            commands_diff(&expected_commands, &commands)
+        );
+    }
+    machine
+}
+
+/// Lists the commands one per line, marking the ones which differ with `-` for expected and `+`
+/// for actual
+fn commands_diff<C: Debug + PartialEq>(expected: &[C], actual: &[C]) -> String {
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => writeln!(diff, "  {:?}", e),
+            (e, a) => {
+                if let Some(e) = e {
+                    writeln!(diff, "- {:?}", e).unwrap();
+                }
+                match a {
+                    Some(a) => writeln!(diff, "+ {:?}", a),
+                    None => Ok(()),
+                }
+            }
+        }
+        .unwrap();
+    }
+    diff
+}
@@ -0,0 +1,130 @@
+//! Handing events to a machine without committing to the result.
+//!
+//! [StateMachine::on_event] consumes the machine, so finding out what an event would do means
+//! cloning the machine first. [TryEvent] does that for any machine implementing [Clone]: events are
+//! applied to a copy inside a [Transaction], which can be inspected, extended with more events, and
+//! then either committed back to the original machine or rolled back.
+
+use crate::{CommandCollection, StateMachine, TransitionError, TransitionResult};
+use core::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Extends cloneable machines with ways to try events out before applying them
+pub trait TryEvent<E, C, CC>: StateMachine<Self, E, C, CC> + Clone + Sized {
+    /// Handles an event on a copy of the machine, leaving the machine itself untouched
+    fn simulate(&self, event: E) -> TransitionResult<Self, Self::Error, C, CC> {
+        self.clone().on_event(event)
+    }
+
+    /// Handles an event on a copy of the machine, returning a transaction holding the would-be
+    /// state and commands. Nothing changes until the transaction is committed.
+    fn try_event(
+        &mut self,
+        event: E,
+    ) -> Result<Transaction<'_, Self, CC>, TransitionError<Self::Error>>
+    where
+        CC: CommandCollection<C>,
+    {
+        Transaction::new(self).and_event(event)
+    }
+
+    /// Handles a batch of events in order on a copy of the machine, returning a transaction
+    /// holding the state after the last of them, and the commands of all of them. If any of them
+    /// fails the machine is left untouched, and the error says which one it was.
+    fn try_events<I>(
+        &mut self,
+        events: I,
+    ) -> Result<Transaction<'_, Self, CC>, BatchError<Self::Error>>
+    where
+        I: IntoIterator<Item = E>,
+        CC: CommandCollection<C>,
+    {
+        let mut transaction = Transaction::new(self);
+        for (index, event) in events.into_iter().enumerate() {
+            transaction = transaction
+                .and_event(event)
+                .map_err(|error| BatchError { index, error })?;
+        }
+        Ok(transaction)
+    }
+}
+
+impl<M, E, C, CC> TryEvent<E, C, CC> for M where M: StateMachine<M, E, C, CC> + Clone {}
+
+/// The result of handling some events on a copy of a machine, which can be committed back to the
+/// machine or rolled back. Dropping a transaction rolls it back.
+#[must_use = "A transaction does nothing unless it is committed"]
+pub struct Transaction<'a, M, CC> {
+    machine: &'a mut M,
+    pending: M,
+    commands: CC,
+}
+
+impl<'a, M: Clone, CC: Default> Transaction<'a, M, CC> {
+    fn new(machine: &'a mut M) -> Self {
+        Self {
+            pending: machine.clone(),
+            machine,
+            commands: CC::default(),
+        }
+    }
+
+    /// The state the machine will be in if the transaction is committed
+    pub fn state(&self) -> &M {
+        &self.pending
+    }
+
+    /// The commands produced by the events handled so far, in order
+    pub fn commands(&self) -> &CC {
+        &self.commands
+    }
+
+    /// Handles another event in the transaction. If it fails, the whole transaction is rolled
+    /// back.
+    pub fn and_event<E, C>(mut self, event: E) -> Result<Self, TransitionError<M::Error>>
+    where
+        M: StateMachine<M, E, C, CC>,
+        CC: CommandCollection<C>,
+    {
+        let (new_state, commands) = self.pending.on_event(event).into_result()?;
+        self.pending = new_state;
+        self.commands.extend(commands);
+        Ok(self)
+    }
+
+    /// Applies the transaction to the machine, returning the commands it produced
+    pub fn commit(self) -> CC {
+        *self.machine = self.pending;
+        self.commands
+    }
+
+    /// Discards the transaction, leaving the machine as it was
+    pub fn rollback(self) {}
+}
+
+/// Says which event of a batch failed, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError<E> {
+    /// The position of the failed event in the batch
+    pub index: usize,
+    /// Why it failed
+    pub error: TransitionError<E>,
+}
+
+impl<E: Display> Display for BatchError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Event {} of the batch failed: {}",
+            self.index, self.error
+        )
+    }
+}
+
+impl<E: Error + 'static> Error for BatchError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,192 @@
+//! Machines whose events borrow their data, rather than owning it
+
+use rustfsm::{
+    fsm,
+    fuzz::{
+        arbitrary::{Arbitrary, Unstructured},
+        fuzz_events,
+    },
+    transaction::TryEvent,
+    StateMachine, TransitionResult,
+};
+
+fsm! {
+    #[derive(Debug, Clone)]
+    #[events(derive(Debug, Clone, PartialEq))]
+    #[fsm(arbitrary)]
+    CardReader, Commands, CardError
+
+    Locked --(CardReadable(&'a [u8]), on_card_readable) --> ReadingCard;
+    ReadingCard --(CardDataChunk(&'a [u8]) | CardDataEnd, on_chunk);
+    ReadingCard --(Verdict(&'b str), on_verdict) --> DoorOpen | Locked;
+    DoorOpen --(DoorClosed)--> Locked
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Commands {
+    ProcessData(Vec<u8>),
+    Log(String),
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Card couldn't be read")]
+pub struct CardError;
+
+#[derive(Debug, Clone, Default)]
+pub struct Locked {}
+impl Locked {
+    fn on_card_readable(&self, data: &[u8]) -> CardReaderTransition {
+        if data.is_empty() {
+            return TransitionResult::Err(CardError);
+        }
+        TransitionResult::ok(
+            vec![Commands::ProcessData(data.to_vec())],
+            ReadingCard { bytes: data.len() },
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReadingCard {
+    bytes: usize,
+}
+impl ReadingCard {
+    fn on_chunk(&mut self, event: CardReaderEvents<'_, '_>) -> Result<Vec<Commands>, CardError> {
+        match event {
+            CardReaderEvents::CardDataChunk(data) => {
+                self.bytes += data.len();
+                Ok(vec![Commands::ProcessData(data.to_vec())])
+            }
+            _ => Ok(vec![Commands::Log(format!("Read {} bytes", self.bytes))]),
+        }
+    }
+
+    fn on_verdict(&self, verdict: &str) -> CardReaderDoorOpenOrLockedTransition {
+        let commands = vec![Commands::Log(verdict.to_string())];
+        if verdict == "accepted" {
+            TransitionResult::ok(commands, DoorOpen {})
+        } else {
+            TransitionResult::ok(commands, Locked {})
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DoorOpen {}
+
+/// Splits a packet of `verdict|data` into the events it holds, borrowing from the packet
+fn parse(packet: &[u8]) -> Vec<CardReaderEvents<'_, '_>> {
+    let split = packet.iter().position(|b| *b == b'|').unwrap();
+    let (verdict, data) = (&packet[..split], &packet[split + 1..]);
+    let (first, rest) = data.split_at(data.len() / 2);
+    vec![
+        CardReaderEvents::CardReadable(first),
+        CardReaderEvents::CardDataChunk(rest),
+        CardReaderEvents::CardDataEnd,
+        CardReaderEvents::Verdict(std::str::from_utf8(verdict).unwrap()),
+    ]
+}
+
+#[test]
+fn handlers_take_borrowed_data_and_return_owned_commands() {
+    let mut machine = CardReader::Locked(Locked {});
+    let mut commands = vec![];
+    {
+        // The events only live as long as the packet they were parsed from, but the machine and
+        // its commands outlive it
+        let packet = b"accepted|abcd".to_vec();
+        for event in parse(&packet) {
+            let (new_state, cmds) = machine.on_event(event).unwrap();
+            machine = new_state;
+            commands.extend(cmds);
+        }
+    }
+    assert!(matches!(machine, CardReader::DoorOpen(_)));
+    assert_eq!(
+        commands,
+        vec![
+            Commands::ProcessData(b"ab".to_vec()),
+            Commands::ProcessData(b"cd".to_vec()),
+            Commands::Log("Read 4 bytes".to_string()),
+            Commands::Log("accepted".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn borrowed_events_can_be_inspected_and_tried() {
+    let data = [1, 2, 3];
+    let event = CardReaderEvents::CardReadable(&data);
+    let mut machine = CardReader::Locked(Locked {});
+    assert_eq!(event.name(), "CardReadable");
+    assert!(machine.can_handle(&event));
+
+    let transaction = machine.try_event(event).unwrap();
+    assert!(matches!(transaction.state(), CardReader::ReadingCard(_)));
+    transaction.rollback();
+    assert!(matches!(
+        machine.on_event(CardReaderEvents::CardReadable(&[])),
+        TransitionResult::Err(CardError)
+    ));
+}
+
+#[test]
+fn arbitrary_events_borrow_from_the_input() {
+    let input = [0, 7, 8, 9, 10];
+    let mut data = Unstructured::new(&input);
+    match CardReaderEvents::arbitrary(&mut data).unwrap() {
+        CardReaderEvents::CardReadable(card) => {
+            assert!(input.as_ptr_range().contains(&card.as_ptr()));
+        }
+        other => panic!("Expected card data, got {:?}", other),
+    }
+}
+
+#[test]
+fn borrowed_events_can_be_fuzzed() {
+    // Read a card, then close the door twice (invalid, so skipped)
+    let machine = fuzz_events(CardReader::Locked(Locked {}), &[0, 7, 8, 9, 4, 2]);
+    assert!(matches!(machine, CardReader::ReadingCard(_)));
+}
+
+mod sink {
+    use rustfsm::{fsm, CommandSink, StateMachine, TransitionResult};
+    use std::convert::Infallible;
+
+    fsm! {
+        #[fsm(command_sink)]
+        Door, String, Infallible
+
+        Closed --(Knock(&'a str), on_knock)--> Open;
+        Open --(Close)--> Closed
+    }
+
+    #[derive(Default)]
+    pub struct Closed {}
+    impl Closed {
+        fn on_knock(&self, who: &str, sink: &mut dyn CommandSink<String>) -> DoorTransition {
+            sink.emit(format!("Welcome, {}", who));
+            TransitionResult::default::<Open>()
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Open {}
+
+    #[test]
+    fn borrowed_events_work_with_command_sinks() {
+        let mut greetings = vec![];
+        let name = String::from("Alice");
+        let door = Door::Closed(Closed {})
+            .on_event_into(DoorEvents::Knock(&name), &mut greetings)
+            .unwrap();
+        assert!(matches!(door, Door::Open(_)));
+        assert!(door.on_event(DoorEvents::Knock(&name)).is_invalid());
+        let door = Door::Open(Open {});
+        assert!(matches!(
+            door.on_event(DoorEvents::Close).unwrap().0,
+            Door::Closed(_)
+        ));
+        assert_eq!(greetings, vec!["Welcome, Alice".to_string()]);
+    }
+}
@@ -2,9 +2,9 @@
 //!
 //! This is the by-hand version, useful to compare to the macro version in the docs
 
-use state_machine_trait::{StateMachine, TransitionResult};
+use rustfsm::{InvalidTransition, Name, StateMachine, TransitionResult};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum CardReader {
     Locked(Locked),
     ReadingCard(ReadingCard),
@@ -40,6 +40,37 @@ impl CardReader {
     }
 }
 
+impl CardReaderEvents {
+    fn name(&self) -> &'static str {
+        match self {
+            CardReaderEvents::CardReadable(_) => "CardReadable",
+            CardReaderEvents::DoorClosed => "DoorClosed",
+            CardReaderEvents::CardAccepted => "CardAccepted",
+            CardReaderEvents::CardRejected => "CardRejected",
+        }
+    }
+}
+
+/// Describes `event` arriving in `state`, which only has transitions for the `accepted` events
+fn invalid(
+    state: &'static str,
+    event: &CardReaderEvents,
+    accepted: &'static [&'static str],
+) -> TransitionResult<CardReader, CardReaderError, Commands> {
+    TransitionResult::InvalidTransition(InvalidTransition {
+        machine: Name::new("CardReader"),
+        state: Name::new(state),
+        event: Name::new(event.name()),
+        accepted: accepted.iter().copied().map(Name::new).collect(),
+    })
+}
+
+impl Default for CardReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StateMachine<CardReader, CardReaderEvents, Commands> for CardReader {
     type Error = CardReaderError;
 
@@ -52,7 +83,7 @@ impl StateMachine<CardReader, CardReaderEvents, Commands> for CardReader {
                     commands.push(Commands::StartBlinkingLight);
                     Self::ReadingCard(ls.on_card_readable(data))
                 }
-                _ => return TransitionResult::InvalidTransition,
+                _ => return invalid("Locked", &event, &["CardReadable"]),
             },
             CardReader::ReadingCard(rc) => match event {
                 CardReaderEvents::CardAccepted => {
@@ -63,11 +94,11 @@ impl StateMachine<CardReader, CardReaderEvents, Commands> for CardReader {
                     commands.push(Commands::StopBlinkingLight);
                     Self::Locked(rc.on_card_rejected())
                 }
-                _ => return TransitionResult::InvalidTransition,
+                _ => return invalid("ReadingCard", &event, &["CardAccepted", "CardRejected"]),
             },
             CardReader::Unlocked(_) => match event {
                 CardReaderEvents::DoorClosed => Self::Locked(Locked {}),
-                _ => return TransitionResult::InvalidTransition,
+                _ => return invalid("Unlocked", &event, &["DoorClosed"]),
             },
         };
         TransitionResult::Ok {
@@ -113,6 +144,7 @@ impl ReadingCard {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rustfsm::assert_trace;
 
     // Should be kept the same the main example doctest
     #[test]
@@ -136,4 +168,52 @@ mod tests {
         let (_, cmds) = cr.on_event(CardReaderEvents::CardAccepted).unwrap();
         assert_eq!(cmds[0], Commands::StopBlinkingLight);
     }
+
+    #[test]
+    fn trace_a_card_reader() {
+        let cr = assert_trace!(CardReader::new();
+            CardReaderEvents::CardReadable("badguy".to_string()) => CardReader::ReadingCard(_),
+                [Commands::ProcessData("badguy".to_string()), Commands::StartBlinkingLight];
+            CardReaderEvents::CardRejected => CardReader::Locked(_), [Commands::StopBlinkingLight];
+            CardReaderEvents::CardReadable("goodguy".to_string()) => CardReader::ReadingCard(_),
+                [Commands::ProcessData("goodguy".to_string()), Commands::StartBlinkingLight];
+            CardReaderEvents::CardAccepted => CardReader::Unlocked(_), [Commands::StopBlinkingLight];
+        );
+        assert!(matches!(cr, CardReader::Unlocked(_)));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Step 2 (`CardReaderEvents::DoorClosed`): invalid transition \
+                    from state ReadingCard"
+    )]
+    fn trace_reports_invalid_transitions() {
+        assert_trace!(CardReader::new();
+            CardReaderEvents::CardReadable("badguy".to_string()) => CardReader::ReadingCard(_),
+                [Commands::ProcessData("badguy".to_string()), Commands::StartBlinkingLight];
+            CardReaderEvents::DoorClosed => CardReader::Locked(_), [];
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Step 1 (`CardReaderEvents::CardReadable(\"badguy\".to_string())`): \
+                    expected state `CardReader::Unlocked(_)`, but the machine is in \
+                    ReadingCard(ReadingCard { card_data: \"badguy\" })"
+    )]
+    fn trace_reports_wrong_state() {
+        assert_trace!(CardReader::new();
+            CardReaderEvents::CardReadable("badguy".to_string()) => CardReader::Unlocked(_), [];
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "commands did not match (- expected, + actual):\n  \
+                               ProcessData(\"badguy\")\n- StopBlinkingLight\n+ StartBlinkingLight\n")]
+    fn trace_reports_command_differences() {
+        assert_trace!(CardReader::new();
+            CardReaderEvents::CardReadable("badguy".to_string()) => CardReader::ReadingCard(_),
+                [Commands::ProcessData("badguy".to_string()), Commands::StopBlinkingLight];
+        );
+    }
 }
@@ -0,0 +1,158 @@
+//! Sending a macro-defined card reader's commands to sinks rather than collecting them
+
+mod common;
+
+// The shared card reader's commands, with a card reader whose handlers emit them into sinks
+use common::{CardData, Commands};
+use rustfsm::{
+    fsm, CommandBuffer, CommandSink, FnSink, StateMachine, TransitionError, TransitionResult,
+};
+use std::{convert::Infallible, sync::mpsc};
+
+fsm! {
+    #[fsm(command_sink)]
+    CardReader, Commands, Infallible
+
+    Locked --(CardReadable(CardData), on_card_readable) --> ReadingCard;
+    ReadingCard --(CardDataChunk(CardData), on_chunk);
+    ReadingCard --(CardAccepted, on_card_accepted) --> DoorOpen;
+    ReadingCard --(CardRejected)--> Locked;
+    DoorOpen --(DoorClosed)--> Locked
+}
+
+#[derive(Default)]
+pub struct Locked {}
+impl Locked {
+    fn on_card_readable(
+        &self,
+        data: CardData,
+        sink: &mut dyn CommandSink<Commands>,
+    ) -> CardReaderTransition {
+        sink.emit(Commands::ProcessData(data));
+        sink.emit(Commands::StartBlinkingLight);
+        TransitionResult::default::<ReadingCard>()
+    }
+}
+
+#[derive(Default)]
+pub struct ReadingCard {}
+impl ReadingCard {
+    fn on_chunk(
+        &mut self,
+        data: CardData,
+        sink: &mut dyn CommandSink<Commands>,
+    ) -> Result<(), Infallible> {
+        sink.emit(Commands::ProcessData(data));
+        Ok(())
+    }
+    fn on_card_accepted(&self, _: &mut dyn CommandSink<Commands>) -> CardReaderTransition {
+        // Returned commands still reach the sink
+        TransitionResult::ok(vec![Commands::StopBlinkingLight], DoorOpen {})
+    }
+}
+
+#[derive(Default)]
+pub struct DoorOpen {}
+
+#[test]
+fn commands_go_to_the_sink() {
+    let mut sink = vec![];
+    let cr = CardReader::Locked(Locked {})
+        .on_event_into(
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            &mut sink,
+        )
+        .unwrap();
+    let cr = cr
+        .on_event_into(
+            CardReaderEvents::CardDataChunk("more".to_string()),
+            &mut sink,
+        )
+        .unwrap();
+    let cr = cr
+        .on_event_into(CardReaderEvents::CardAccepted, &mut sink)
+        .unwrap();
+    assert!(matches!(cr, CardReader::DoorOpen(_)));
+    assert_eq!(
+        sink,
+        vec![
+            Commands::ProcessData("goodguy".to_string()),
+            Commands::StartBlinkingLight,
+            Commands::ProcessData("more".to_string()),
+            Commands::StopBlinkingLight,
+        ]
+    );
+
+    assert!(matches!(
+        cr.on_event_into(CardReaderEvents::CardAccepted, &mut sink),
+        Err(TransitionError::InvalidTransition(_))
+    ));
+}
+
+#[test]
+fn on_event_still_returns_commands() {
+    let (_, cmds) = CardReader::Locked(Locked {})
+        .on_event(CardReaderEvents::CardReadable("goodguy".to_string()))
+        .unwrap();
+    assert_eq!(
+        cmds,
+        vec![
+            Commands::ProcessData("goodguy".to_string()),
+            Commands::StartBlinkingLight
+        ]
+    );
+}
+
+#[test]
+fn any_sink_will_do() {
+    let event = || CardReaderEvents::CardReadable("goodguy".to_string());
+
+    let mut buffer = CommandBuffer::<Commands, 2>::new();
+    CardReader::Locked(Locked {})
+        .on_event_into(event(), &mut buffer)
+        .unwrap();
+    assert_eq!(buffer.len(), 2);
+
+    let mut count = 0;
+    CardReader::Locked(Locked {})
+        .on_event_into(event(), &mut FnSink(|_| count += 1))
+        .unwrap();
+    assert_eq!(count, 2);
+
+    let (mut tx, rx) = mpsc::channel();
+    CardReader::Locked(Locked {})
+        .on_event_into(event(), &mut tx)
+        .unwrap();
+    assert_eq!(rx.try_iter().count(), 2);
+}
+
+fsm! {
+    Door, DoorCommand, Infallible
+
+    Closed --(Open, on_open) --> Opened
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DoorCommand {
+    Beep,
+}
+
+pub struct Closed {}
+impl Closed {
+    fn on_open(&self) -> DoorTransition {
+        TransitionResult::ok(vec![DoorCommand::Beep], Opened {})
+    }
+}
+
+#[derive(Default)]
+pub struct Opened {}
+
+#[test]
+fn machines_without_the_option_can_use_sinks_too() {
+    let mut sink = vec![];
+    let door = Door::Closed(Closed {})
+        .on_event_into(DoorEvents::Open, &mut sink)
+        .unwrap();
+    assert!(matches!(door, Door::Opened(_)));
+    assert_eq!(sink, vec![DoorCommand::Beep]);
+}
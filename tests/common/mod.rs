@@ -0,0 +1,76 @@
+//! The card reader most of the tests drive. It reads a card, then opens the door if the card is
+//! accepted, and fails with an error if the card is blank.
+
+// Each test crate only uses some of the fixture
+#![allow(dead_code)]
+
+use rustfsm::{fsm, TransitionResult};
+
+fsm! {
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    #[events(derive(Debug, Clone, PartialEq))]
+    #[fsm(proptest, arbitrary)]
+    CardReader, Commands, CardError
+
+    Locked --(CardReadable(CardData), on_card_readable) --> ReadingCard;
+    ReadingCard --(CardAccepted, on_card_accepted) --> DoorOpen;
+    ReadingCard --(CardRejected, on_card_rejected) --> Locked;
+    DoorOpen --(DoorClosed, on_door_closed) --> Locked
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Commands {
+    StartBlinkingLight,
+    StopBlinkingLight,
+    ProcessData(CardData),
+}
+
+pub type CardData = String;
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("The card was blank")]
+pub struct CardError;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Locked {}
+impl Locked {
+    fn on_card_readable(&self, data: CardData) -> CardReaderTransition {
+        if data.is_empty() {
+            return TransitionResult::Err(CardError);
+        }
+        TransitionResult::ok(
+            vec![
+                Commands::ProcessData(data.clone()),
+                Commands::StartBlinkingLight,
+            ],
+            ReadingCard { card_data: data },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ReadingCard {
+    pub card_data: CardData,
+}
+impl ReadingCard {
+    fn on_card_accepted(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![Commands::StopBlinkingLight], DoorOpen {})
+    }
+    fn on_card_rejected(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![Commands::StopBlinkingLight], Locked {})
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct DoorOpen {}
+impl DoorOpen {
+    fn on_door_closed(&self) -> CardReaderTransition {
+        TransitionResult::ok(vec![], Locked {})
+    }
+}
+
+/// Reads a card with `data` on it, starting from the locked state
+pub fn read(data: &str) -> CardReaderTransition {
+    use rustfsm::StateMachine;
+    CardReader::Locked(Locked {}).on_event(CardReaderEvents::CardReadable(data.to_string()))
+}
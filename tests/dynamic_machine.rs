@@ -0,0 +1,238 @@
+//! A card reader defined at runtime, as if read from a configuration file
+
+use rustfsm::{
+    dynamic::{DefinitionError, DynamicMachine, DynamicMachineBuilder},
+    metrics::{InMemoryMetrics, MetricsObserver},
+    observe::ObserveTransitions,
+    transaction::TryEvent,
+    DescribeMachine, Name, StateMachine, TransitionError, TransitionInfo, TransitionResult,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Commands {
+    ProcessData(String),
+    StopBlinkingLight,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("The card was blank")]
+pub struct CardError;
+
+type CardReader = DynamicMachine<Commands, CardError, String>;
+
+/// Transitions as they might be listed in a configuration file, as `from event to`
+const CONFIG: &str = "
+    Locked CardReadable ReadingCard
+    ReadingCard CardAccepted DoorOpen
+    ReadingCard CardRejected Locked
+    DoorOpen DoorClosed Locked
+";
+
+fn card_reader() -> CardReader {
+    let mut builder = CardReader::builder("CardReader");
+    for line in CONFIG.lines().filter(|l| !l.trim().is_empty()) {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words[1] {
+            "CardReadable" => builder.transition_with(words[0], words[1], words[2], |data| {
+                if data.is_empty() {
+                    return Err(CardError);
+                }
+                Ok(vec![Commands::ProcessData(data)])
+            }),
+            "CardAccepted" | "CardRejected" => {
+                builder.transition_with(words[0], words[1], words[2], |_| {
+                    Ok(vec![Commands::StopBlinkingLight])
+                })
+            }
+            _ => builder.transition(words[0], words[1], words[2]),
+        };
+    }
+    builder.build("Locked").unwrap()
+}
+
+#[test]
+fn runs_like_a_macro_defined_machine() {
+    let cr = card_reader();
+    let event = cr.event("CardReadable", "badguy".to_string()).unwrap();
+    let (cr, cmds) = cr.on_event(event).unwrap();
+    assert_eq!(cr.name(), "ReadingCard");
+    assert_eq!(cmds, vec![Commands::ProcessData("badguy".to_string())]);
+
+    let event = cr.event("CardRejected", String::new()).unwrap();
+    let (cr, cmds) = cr.on_event(event).unwrap();
+    assert_eq!(cr.name(), "Locked");
+    assert_eq!(cmds, vec![Commands::StopBlinkingLight]);
+
+    let blank = cr.event("CardReadable", String::new()).unwrap();
+    assert!(matches!(
+        cr.on_event(blank),
+        TransitionResult::Err(CardError)
+    ));
+}
+
+#[test]
+fn describes_its_definition() {
+    let cr = card_reader();
+    assert_eq!(cr.machine_name(), "CardReader");
+    assert_eq!(cr.state_names(), &["Locked", "ReadingCard", "DoorOpen"][..]);
+    assert_eq!(
+        cr.event_names(),
+        &["CardReadable", "CardAccepted", "CardRejected", "DoorClosed"][..]
+    );
+    assert_eq!(cr.transitions().len(), 4);
+    assert_eq!(
+        cr.transitions()[1],
+        TransitionInfo {
+            from: Name::new("ReadingCard"),
+            event: Name::new("CardAccepted"),
+            to: Name::new("DoorOpen"),
+            handler: None,
+            internal: false,
+            doc: None,
+        }
+    );
+
+    let reading = cr.with_state("ReadingCard").unwrap();
+    assert_eq!(
+        reading.available_events(),
+        &["CardAccepted", "CardRejected"]
+    );
+    assert!(!cr.can_handle(&cr.event("DoorClosed", String::new()).unwrap()));
+    assert!(cr.with_state("Unlocked").is_none());
+    assert!(cr.event("PowerLost", String::new()).is_none());
+}
+
+#[test]
+fn invalid_transitions_are_described() {
+    let cr = card_reader();
+    let event = cr.event("DoorClosed", String::new()).unwrap();
+    match cr.on_event(event).into_result() {
+        Err(TransitionError::InvalidTransition(i)) => assert_eq!(
+            i.to_string(),
+            "CardReader cannot handle event `DoorClosed` in state `Locked`, which accepts \
+             `CardReadable`"
+        ),
+        _ => panic!("Expected an invalid transition"),
+    }
+}
+
+#[test]
+fn builds_from_ids() {
+    let mut builder = DynamicMachineBuilder::<u8>::new("Toggle");
+    let off = builder.state("Off");
+    let on = builder.state("On");
+    let flip = builder.event("Flip");
+    builder
+        .transition_with(off, flip, on, |()| Ok(vec![1]))
+        .transition_with(on, flip, off, |()| Ok(vec![0]));
+    let mut toggle = builder.build(off).unwrap();
+    assert_eq!(toggle.state_id(), off);
+
+    let flip_event = toggle.event_by_id(flip, ()).unwrap();
+    let transaction = toggle.try_event(flip_event.clone()).unwrap();
+    assert_eq!(transaction.state().name(), "On");
+    assert_eq!(transaction.commit(), vec![1]);
+    assert_eq!(toggle.state_id(), on);
+    let (toggle, cmds) = toggle.on_event(flip_event).unwrap();
+    assert_eq!((toggle.name(), cmds), ("Off", vec![0]));
+}
+
+#[test]
+fn mistakes_are_reported_when_building() {
+    let mut builder = DynamicMachineBuilder::<u8>::new("Toggle");
+    builder
+        .transition("Off", "Flip", "On")
+        .transition("Off", "Flip", "Off");
+    assert_eq!(
+        builder.build("Off").unwrap_err(),
+        DefinitionError::DuplicateTransition {
+            state: "Off".to_string(),
+            event: "Flip".to_string()
+        }
+    );
+
+    // IDs only mean anything to the builder which handed them out, even where the other builder
+    // has a state or event at the same index
+    let mut other = DynamicMachineBuilder::<u8>::new("Other");
+    let elsewhere = other.state("Elsewhere");
+    let wiggle = other.event("Wiggle");
+    let mut builder = DynamicMachineBuilder::<u8>::new("Toggle");
+    builder.transition("Off", "Flip", "On");
+    assert_eq!(
+        builder.build("Broken").unwrap_err(),
+        DefinitionError::UnknownInitialState("Broken".to_string())
+    );
+    assert_eq!(
+        builder.build(elsewhere).unwrap_err(),
+        DefinitionError::UnknownState(elsewhere)
+    );
+    builder.transition("On", wiggle, "Off");
+    assert_eq!(
+        builder.build("Off").unwrap_err(),
+        DefinitionError::UnknownEvent(wiggle)
+    );
+    assert_eq!(
+        DefinitionError::UnknownEvent(wiggle).to_string(),
+        "Event ID 0 was handed out by a different builder"
+    );
+}
+
+#[test]
+fn events_only_work_on_machines_from_the_same_builder() {
+    let mut builder = DynamicMachineBuilder::<u8>::new("Toggle");
+    let flip = builder.event("Flip");
+    builder.transition("Off", flip, "On");
+    let toggle = builder.build("Off").unwrap();
+
+    let mut other = DynamicMachineBuilder::<u8>::new("Other");
+    let wiggle = other.event("Wiggle");
+    other.transition("Off", wiggle, "On");
+    let other = other.build("Off").unwrap();
+
+    assert!(toggle.event_by_id(wiggle, ()).is_none());
+    let foreign = other.event_by_id(wiggle, ()).unwrap();
+    assert!(!toggle.can_handle(&foreign));
+    assert!(toggle.clone().on_event(foreign).is_invalid());
+    let flip = toggle.event_by_id(flip, ()).unwrap();
+    assert_eq!(toggle.on_event(flip).unwrap().0.name(), "On");
+}
+
+#[test]
+fn building_again_reuses_the_definition() {
+    let mut builder = DynamicMachineBuilder::<u8>::new("Toggle");
+    builder.transition("Off", "Flip", "On");
+    let first = builder.build("Off").unwrap();
+    let second = builder.build("On").unwrap();
+    assert!(std::ptr::eq(first.state_names(), second.state_names()));
+
+    builder.transition("On", "Flip", "Off");
+    let third = builder.build("On").unwrap();
+    assert!(!std::ptr::eq(first.state_names(), third.state_names()));
+    assert!(third.can_handle(&third.event("Flip", ()).unwrap()));
+    assert!(!second.can_handle(&second.event("Flip", ()).unwrap()));
+    // Names are shared between the builds rather than allocated again
+    assert!(std::ptr::eq(
+        first.state_names()[0].as_str(),
+        third.state_names()[0].as_str()
+    ));
+}
+
+#[test]
+fn can_be_observed() {
+    let cr = card_reader();
+    let mut metrics = MetricsObserver::new(InMemoryMetrics::new());
+    let event = cr.event("CardReadable", "goodguy".to_string()).unwrap();
+    let (cr, _) = cr.on_event_observed(event, &mut metrics).unwrap();
+    let event = cr.event("DoorClosed", String::new()).unwrap();
+    assert!(cr.on_event_observed(event, &mut metrics).is_invalid());
+
+    let metrics = metrics.into_inner();
+    assert_eq!(
+        metrics.transition_count("CardReader", "Locked", "CardReadable", "ReadingCard"),
+        1
+    );
+    assert_eq!(
+        metrics.invalid_count("CardReader", "ReadingCard", "DoorClosed"),
+        1
+    );
+}
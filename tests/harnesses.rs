@@ -0,0 +1,254 @@
+//! Property testing, fuzzing and model checking the shared card reader
+
+mod common;
+
+use common::*;
+use rustfsm::{
+    fuzz::{
+        arbitrary::{Arbitrary, Unstructured},
+        fuzz_events,
+    },
+    model_check::{ModelChecker, Violation},
+    proptest_support::{
+        check_event_sequences,
+        proptest::test_runner::{Config, TestError},
+    },
+};
+
+#[test]
+fn light_only_blinks_while_reading() {
+    // The invariant holds, so the only way to fail is reading a blank card
+    let result = check_event_sequences(
+        Config::default(),
+        20,
+        || CardReader::Locked(Locked {}),
+        |cr, cmds| {
+            let reading = matches!(cr, CardReader::ReadingCard(_));
+            if cmds.contains(&Commands::StartBlinkingLight) && !reading {
+                return Err(format!("Light started blinking in {}", cr));
+            }
+            Ok(())
+        },
+    );
+    match result {
+        Err(TestError::Fail(reason, events)) => {
+            assert_eq!(
+                reason.message(),
+                "Handler failed at step 0: The card was blank"
+            );
+            assert_eq!(events, vec![CardReaderEvents::CardReadable(String::new())]);
+        }
+        _ => panic!("Reading a blank card should have failed"),
+    }
+}
+
+#[test]
+fn failing_sequences_are_shrunk() {
+    // Only closing the door is valid while it's open, so that's all a failing sequence needs
+    let result = check_event_sequences(
+        Config::default(),
+        20,
+        || CardReader::DoorOpen(DoorOpen {}),
+        |cr, _| match cr {
+            CardReader::Locked(_) => Err("The door closed".to_string()),
+            _ => Ok(()),
+        },
+    );
+    match result {
+        Err(TestError::Fail(reason, events)) => {
+            assert_eq!(
+                reason.message(),
+                "Invariant failed at step 0: The door closed"
+            );
+            assert_eq!(events, vec![CardReaderEvents::DoorClosed]);
+        }
+        _ => panic!("The door should have been closed"),
+    }
+}
+
+#[test]
+fn events_are_decoded_from_bytes() {
+    // Strings take their length from the end of the input
+    let mut data = Unstructured::new(&[0, b'a', 3, 1, 1]);
+    assert_eq!(
+        CardReaderEvents::arbitrary(&mut data).unwrap(),
+        CardReaderEvents::CardReadable("a".to_string())
+    );
+    assert_eq!(
+        CardReaderEvents::arbitrary(&mut data).unwrap(),
+        CardReaderEvents::DoorClosed
+    );
+    assert_eq!(
+        CardReaderEvents::arbitrary(&mut data).unwrap(),
+        CardReaderEvents::CardAccepted
+    );
+}
+
+#[test]
+fn fuzz_input_drives_the_machine() {
+    // Read a card, close the door (invalid, so skipped), then accept the card
+    let cr = fuzz_events(CardReader::Locked(Locked {}), &[0, b'a', 3, 1, 1]);
+    assert!(matches!(cr, CardReader::DoorOpen(_)));
+
+    // Read a blank card (an error, so skipped), then accept it twice (invalid)
+    let cr = fuzz_events(CardReader::Locked(Locked {}), &[0, b'a', 1, 3]);
+    assert!(matches!(cr, CardReader::Locked(_)));
+}
+
+fn all_events(_: &CardReader) -> Vec<CardReaderEvents> {
+    vec![
+        CardReaderEvents::CardReadable("".to_string()),
+        CardReaderEvents::CardReadable("goodguy".to_string()),
+        CardReaderEvents::CardAccepted,
+        CardReaderEvents::CardRejected,
+        CardReaderEvents::DoorClosed,
+    ]
+}
+
+#[test]
+fn finds_shortest_counterexamples() {
+    let report = ModelChecker::new(CardReader::Locked(Locked {}), all_events)
+        .invariant("door stays shut", |cr, _| match cr {
+            CardReader::DoorOpen(_) => Err("The door is open".to_string()),
+            _ => Ok(()),
+        })
+        .run();
+    assert!(!report.is_ok());
+    assert!(report.complete);
+    assert_eq!(report.counterexamples.len(), 2);
+    assert_eq!(
+        report.counterexamples[0].events,
+        vec![CardReaderEvents::CardReadable("".to_string())]
+    );
+    assert_eq!(
+        report.counterexamples[0].violation,
+        Violation::HandlerError("The card was blank".to_string())
+    );
+    assert_eq!(
+        report.counterexamples[1].events,
+        vec![
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            CardReaderEvents::CardAccepted
+        ]
+    );
+    assert_eq!(
+        report.counterexamples[1].to_string(),
+        "Invariant `door stays shut` violated: The door is open after events \
+         [CardReadable(\"goodguy\"), CardAccepted]"
+    );
+}
+
+#[test]
+fn explores_every_reachable_state() {
+    let report = ModelChecker::new(CardReader::Locked(Locked {}), |_| {
+        vec![
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            CardReaderEvents::CardAccepted,
+            CardReaderEvents::CardRejected,
+            CardReaderEvents::DoorClosed,
+        ]
+    })
+    .invariant("light only starts blinking while reading", |cr, cmds| {
+        if cmds.contains(&Commands::StartBlinkingLight) && cr.name() != "ReadingCard" {
+            return Err(format!("Light started blinking in {}", cr));
+        }
+        Ok(())
+    })
+    .run();
+    assert!(report.is_ok(), "{}", report);
+    assert!(report.complete);
+    assert_eq!(report.states_explored, 3);
+}
+
+#[test]
+fn stops_at_depth_bound() {
+    let report = ModelChecker::new(CardReader::Locked(Locked {}), |_| {
+        vec![
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            CardReaderEvents::CardAccepted,
+        ]
+    })
+    .max_depth(1)
+    .run();
+    assert!(report.is_ok());
+    assert!(!report.complete);
+    assert_eq!(report.states_explored, 2);
+}
+
+/// A machine whose handlers misbehave in ways the card reader's don't
+mod gate {
+    use rustfsm::{fsm, fuzz::fuzz_events, model_check::ModelChecker, TransitionResult};
+
+    fsm! {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[events(derive(Debug, Clone, PartialEq))]
+        #[fsm(arbitrary)]
+        Gate, u8, GateError
+
+        A --(X(u8), on_x)--> B;
+        B --(Y, on_y)--> A;
+        B --(Z, on_z)--> A
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("Gate jammed")]
+    pub struct GateError;
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub struct A {}
+    impl A {
+        fn on_x(&self, data: u8) -> GateTransition {
+            // A bug for the fuzzer to find
+            if data == 0xFF {
+                panic!("Gate data overflowed");
+            }
+            TransitionResult::ok(vec![], B {})
+        }
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub struct B {}
+    impl B {
+        fn on_y(&self) -> GateTransition {
+            TransitionResult::Err(GateError)
+        }
+        fn on_z(&self) -> GateTransition {
+            TransitionResult::ok(vec![1], A {})
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Gate data overflowed")]
+    fn handler_panics_are_propagated() {
+        fuzz_events(Gate::A(A {}), &[0, 0xFF]);
+    }
+
+    #[test]
+    fn failures_past_the_depth_bound_make_the_search_incomplete() {
+        let report = ModelChecker::new(Gate::A(A {}), |_| vec![GateEvents::X(0), GateEvents::Y])
+            .max_depth(1)
+            .run();
+        assert!(report.is_ok());
+        assert!(!report.complete);
+
+        // Z only leads back to a state already seen, but its commands break the invariant
+        let report = ModelChecker::new(Gate::A(A {}), |_| vec![GateEvents::X(0), GateEvents::Z])
+            .invariant("no commands", |_, cmds| {
+                if cmds.is_empty() {
+                    Ok(())
+                } else {
+                    Err("Produced a command".to_string())
+                }
+            })
+            .max_depth(1)
+            .run();
+        assert!(report.is_ok());
+        assert!(!report.complete);
+
+        let report = ModelChecker::new(Gate::A(A {}), |_| vec![GateEvents::X(0), GateEvents::Z])
+            .max_depth(1)
+            .run();
+        assert!(report.is_ok());
+        assert!(report.complete);
+    }
+}
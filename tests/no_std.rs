@@ -0,0 +1,52 @@
+//! Builds the `no_std` examples on their own, so they get only the features they ask for rather
+//! than those the rest of the workspace enables, and for a target which has no `std` at all
+
+use std::{env, fs, path::Path, process::Command};
+
+/// A bare-metal target, which the examples can only build for if nothing they use needs `std`
+const NO_STD_TARGET: &str = "thumbv7em-none-eabihf";
+
+fn cargo(args: &[&str]) {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(args)
+        .current_dir(root)
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"),
+        )
+        .status()
+        .expect("Couldn't run cargo");
+    assert!(status.success(), "`cargo {}` failed", args.join(" "));
+}
+
+fn target_installed(target: &str) -> bool {
+    Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .args(["--print", "target-libdir", "--target", target])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| fs::read_dir(String::from_utf8_lossy(&out.stdout).trim()).ok())
+        .is_some_and(|mut libs| {
+            libs.any(|lib| {
+                lib.is_ok_and(|lib| lib.file_name().to_string_lossy().starts_with("libcore-"))
+            })
+        })
+}
+
+#[test]
+fn examples_build_without_std() {
+    assert!(
+        target_installed(NO_STD_TARGET),
+        "Install the target with `rustup target add {}` to run this check",
+        NO_STD_TARGET
+    );
+    for example in ["no_std_example", "no_alloc_example"] {
+        cargo(&["build", "-p", example, "--target", NO_STD_TARGET]);
+    }
+}
+
+#[test]
+fn no_alloc_example_passes_without_alloc() {
+    cargo(&["test", "-p", "no_alloc_example"]);
+}
@@ -0,0 +1,148 @@
+//! Tracking transition coverage and collecting metrics from the shared card reader
+
+mod common;
+
+use common::*;
+use rustfsm::{
+    coverage::TransitionCoverage,
+    metrics::{InMemoryMetrics, MetricsObserver},
+    observe::ObserveTransitions,
+    Name, TransitionResult,
+};
+
+#[test]
+fn reports_uncovered_transitions() {
+    let mut coverage = TransitionCoverage::new::<CardReader>();
+    let cr = CardReader::Locked(Locked {});
+    let (cr, _) = cr
+        .on_event_observed(
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            &mut coverage,
+        )
+        .unwrap();
+    let (cr, _) = cr
+        .on_event_observed(CardReaderEvents::CardRejected, &mut coverage)
+        .unwrap();
+    let (cr, _) = cr
+        .on_event_observed(
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            &mut coverage,
+        )
+        .unwrap();
+    // Invalid transitions and handler errors aren't counted
+    assert!(matches!(
+        cr.on_event_observed(CardReaderEvents::DoorClosed, &mut coverage),
+        TransitionResult::InvalidTransition(_)
+    ));
+    assert!(matches!(
+        CardReader::Locked(Locked {})
+            .on_event_observed(CardReaderEvents::CardReadable(String::new()), &mut coverage),
+        TransitionResult::Err(CardError)
+    ));
+    // As if a handler went somewhere the definition doesn't say it does
+    coverage.record(
+        &Name::new("ReadingCard"),
+        &Name::new("CardAccepted"),
+        &Name::new("Locked"),
+    );
+
+    let uncovered: Vec<_> = coverage
+        .uncovered()
+        .iter()
+        .map(|t| (t.from.as_str(), t.event.as_str(), t.to.as_str()))
+        .collect();
+    assert_eq!(
+        uncovered,
+        vec![
+            ("ReadingCard", "CardAccepted", "DoorOpen"),
+            ("DoorOpen", "DoorClosed", "Locked")
+        ]
+    );
+    assert_eq!(coverage.transitions()[0].1, 2);
+    assert_eq!(
+        coverage.undeclared(),
+        &[(
+            Name::new("ReadingCard"),
+            Name::new("CardAccepted"),
+            Name::new("Locked")
+        )]
+    );
+    assert_eq!(coverage.ratio(), 0.5);
+    assert_eq!(
+        coverage.to_string(),
+        "Transition coverage for CardReader: 50%
+      ok Locked --(CardReadable)--> ReadingCard [2 hits]
+  MISSED ReadingCard --(CardAccepted)--> DoorOpen [0 hits]
+      ok ReadingCard --(CardRejected)--> Locked [1 hits]
+  MISSED DoorOpen --(DoorClosed)--> Locked [0 hits]
+  UNDECLARED ReadingCard --(CardAccepted)--> Locked
+"
+    );
+    assert_eq!(
+        coverage.to_json(),
+        "{\"machine\":\"CardReader\",\"transitions\":[\
+         {\"from\":\"Locked\",\"event\":\"CardReadable\",\"to\":\"ReadingCard\",\"hits\":2},\
+         {\"from\":\"ReadingCard\",\"event\":\"CardAccepted\",\"to\":\"DoorOpen\",\"hits\":0},\
+         {\"from\":\"ReadingCard\",\"event\":\"CardRejected\",\"to\":\"Locked\",\"hits\":1},\
+         {\"from\":\"DoorOpen\",\"event\":\"DoorClosed\",\"to\":\"Locked\",\"hits\":0}],\
+         \"undeclared\":[{\"from\":\"ReadingCard\",\"event\":\"CardAccepted\",\"to\":\"Locked\"}]}"
+    );
+}
+
+#[test]
+fn counts_transitions() {
+    let mut metrics = MetricsObserver::new(InMemoryMetrics::new());
+    let cr = CardReader::Locked(Locked {});
+    let (cr, _) = cr
+        .on_event_observed(
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            &mut metrics,
+        )
+        .unwrap();
+    let (cr, _) = cr
+        .on_event_observed(CardReaderEvents::CardRejected, &mut metrics)
+        .unwrap();
+    assert!(matches!(
+        cr.on_event_observed(CardReaderEvents::DoorClosed, &mut metrics),
+        TransitionResult::InvalidTransition(_)
+    ));
+    let cr = CardReader::Locked(Locked {});
+    assert!(matches!(
+        cr.on_event_observed(CardReaderEvents::CardReadable(String::new()), &mut metrics),
+        TransitionResult::Err(CardError)
+    ));
+
+    let metrics = metrics.into_inner();
+    assert_eq!(
+        metrics.transition_count("CardReader", "Locked", "CardReadable", "ReadingCard"),
+        1
+    );
+    assert_eq!(
+        metrics.transition_count("CardReader", "ReadingCard", "CardRejected", "Locked"),
+        1
+    );
+    assert_eq!(
+        metrics.transition_count("CardReader", "ReadingCard", "CardAccepted", "DoorOpen"),
+        0
+    );
+    assert_eq!(metrics.transitions().count(), 2);
+    assert_eq!(
+        metrics.invalid_count("CardReader", "Locked", "DoorClosed"),
+        1
+    );
+    assert_eq!(
+        metrics.error_count("CardReader", "Locked", "CardReadable"),
+        1
+    );
+
+    // Latency is recorded whatever the outcome
+    let latency = metrics
+        .latency("CardReader", "Locked", "CardReadable")
+        .unwrap();
+    assert_eq!(latency.count(), 2);
+    assert_eq!(latency.buckets().map(|(_, n)| n).sum::<u64>(), 2);
+    assert_eq!(latency.buckets().last().unwrap().0, None);
+    assert!(metrics
+        .latency("CardReader", "DoorOpen", "DoorClosed")
+        .is_none());
+}
@@ -0,0 +1,177 @@
+//! Checks the spans and events emitted by the shared card reader with the `tracing` feature on
+
+mod common;
+
+use common::*;
+use rustfsm::{StateMachine, TransitionResult};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Metadata, Subscriber,
+};
+
+type Fields = HashMap<String, String>;
+
+/// Records the fields of every span and event
+#[derive(Clone, Default)]
+struct Capture(Arc<Captured>);
+
+#[derive(Default)]
+struct Captured {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<Fields>>,
+    events: Mutex<Vec<(Level, Fields)>>,
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::new();
+        span.record(&mut FieldVisitor(&mut fields));
+        self.0.spans.lock().unwrap().push(fields);
+        Id::from_u64(self.0.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.0.spans.lock().unwrap();
+        values.record(&mut FieldVisitor(&mut spans[span.into_u64() as usize - 1]));
+    }
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.0
+            .events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), fields));
+    }
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+}
+
+fn fields(pairs: &[(&str, &str)]) -> Fields {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn transitions_are_traced() {
+    let capture = Capture::default();
+    tracing::subscriber::with_default(capture.clone(), || {
+        let cr = CardReader::Locked(Locked {});
+        let (cr, _) = cr
+            .on_event(CardReaderEvents::CardReadable("goodguy".to_string()))
+            .unwrap();
+        assert!(matches!(
+            cr.on_event(CardReaderEvents::CardReadable("badguy".to_string())),
+            TransitionResult::InvalidTransition(_)
+        ));
+    });
+
+    let spans = capture.0.spans.lock().unwrap();
+    assert_eq!(
+        *spans,
+        vec![
+            fields(&[
+                ("machine", "CardReader"),
+                ("from", "Locked"),
+                ("event", "CardReadable"),
+                ("to", "ReadingCard"),
+                ("outcome", "ok"),
+                ("commands", "2"),
+            ]),
+            fields(&[
+                ("machine", "CardReader"),
+                ("from", "ReadingCard"),
+                ("event", "CardReadable"),
+                ("outcome", "invalid"),
+            ]),
+        ]
+    );
+    let events = capture.0.events.lock().unwrap();
+    assert_eq!(
+        *events,
+        vec![(
+            Level::WARN,
+            fields(&[
+                ("machine", "CardReader"),
+                ("from", "ReadingCard"),
+                ("event", "CardReadable"),
+                ("message", "Invalid transition"),
+            ])
+        )]
+    );
+}
+
+mod sink {
+    use super::{fields, Capture};
+    use rustfsm::{fsm, CommandSink, StateMachine, TransitionResult};
+    use std::convert::Infallible;
+
+    fsm! {
+        #[fsm(command_sink)]
+        Door, u8, Infallible
+
+        Closed --(Open, on_open)--> Opened
+    }
+
+    #[derive(Default)]
+    pub struct Closed {}
+    impl Closed {
+        fn on_open(&self, sink: &mut dyn CommandSink<u8>) -> DoorTransition {
+            sink.emit(1);
+            sink.emit(2);
+            TransitionResult::ok(vec![3], Opened {})
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Opened {}
+
+    #[test]
+    fn commands_emitted_into_the_sink_are_counted() {
+        let capture = Capture::default();
+        let mut commands = vec![];
+        tracing::subscriber::with_default(capture.clone(), || {
+            Door::Closed(Closed {})
+                .on_event_into(DoorEvents::Open, &mut commands)
+                .unwrap();
+        });
+        assert_eq!(commands, vec![1, 2, 3]);
+        assert_eq!(
+            *capture.0.spans.lock().unwrap(),
+            vec![fields(&[
+                ("machine", "Door"),
+                ("from", "Closed"),
+                ("event", "Open"),
+                ("to", "Opened"),
+                ("outcome", "ok"),
+                ("commands", "3"),
+            ])]
+        );
+    }
+}
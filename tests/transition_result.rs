@@ -0,0 +1,201 @@
+//! Combining, inspecting and trying out the results of the shared card reader's transitions
+
+mod common;
+
+use common::*;
+use rustfsm::{
+    transaction::{BatchError, TryEvent},
+    InvalidTransition, Name, StateMachine, TransitionError, TransitionResult,
+};
+
+#[test]
+fn transitions_can_be_chained() {
+    let (cr, cmds) = read("goodguy")
+        .and_then(|cr| cr.on_event(CardReaderEvents::CardAccepted))
+        .and_then(|cr| cr.on_event(CardReaderEvents::DoorClosed))
+        .unwrap();
+    assert!(matches!(cr, CardReader::Locked(_)));
+    assert_eq!(
+        cmds,
+        vec![
+            Commands::ProcessData("goodguy".to_string()),
+            Commands::StartBlinkingLight,
+            Commands::StopBlinkingLight
+        ]
+    );
+
+    let invalid = read("goodguy").and_then(|cr| cr.on_event(CardReaderEvents::DoorClosed));
+    assert!(invalid.is_invalid());
+    let failed = read("").and_then(|cr| cr.on_event(CardReaderEvents::CardAccepted));
+    assert!(failed.is_err());
+}
+
+#[test]
+fn parts_of_results_can_be_mapped() {
+    let (name, cmds) = read("goodguy")
+        .map_state(|cr| cr.name())
+        .map_commands(|cmd| match cmd {
+            Commands::ProcessData(data) => data.len(),
+            _ => 0,
+        })
+        .unwrap();
+    assert_eq!(name, "ReadingCard");
+    assert_eq!(cmds, vec![7, 0]);
+
+    assert!(matches!(
+        read("").map_err(|e| e.to_string()),
+        TransitionResult::Err(e) if e == "The card was blank"
+    ));
+}
+
+#[test]
+fn results_convert_into_std_results() {
+    assert!(read("goodguy").is_ok());
+    let (cr, cmds) = read("goodguy").into_result().unwrap();
+    assert!(matches!(cr, CardReader::ReadingCard(_)));
+    assert_eq!(cmds.len(), 2);
+
+    let err = read("").into_result().unwrap_err();
+    assert_eq!(err, TransitionError::Handler(CardError));
+    assert_eq!(err.to_string(), "Transition failed: The card was blank");
+
+    let invalid: Result<_, _> = CardReader::Locked(Locked {})
+        .on_event(CardReaderEvents::DoorClosed)
+        .into();
+    assert!(matches!(
+        invalid.unwrap_err(),
+        TransitionError::InvalidTransition(_)
+    ));
+}
+
+#[test]
+fn invalid_transitions_describe_the_state() {
+    let cr = read("goodguy").unwrap().0;
+    let invalid = match cr.on_event(CardReaderEvents::DoorClosed) {
+        TransitionResult::InvalidTransition(invalid) => invalid,
+        _ => panic!("Transition should be invalid"),
+    };
+    assert_eq!(
+        invalid,
+        InvalidTransition {
+            machine: Name::new("CardReader"),
+            state: Name::new("ReadingCard"),
+            event: Name::new("DoorClosed"),
+            accepted: vec![Name::new("CardAccepted"), Name::new("CardRejected")].into(),
+        }
+    );
+    assert_eq!(
+        invalid.to_string(),
+        "CardReader cannot handle event `DoorClosed` in state `ReadingCard`, which accepts \
+         `CardAccepted`, `CardRejected`"
+    );
+
+    let err = CardReader::Locked(Locked {})
+        .on_event(CardReaderEvents::CardAccepted)
+        .into_result()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "CardReader cannot handle event `CardAccepted` in state `Locked`, which accepts \
+         `CardReadable`"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Card should be read: Handler(CardError)")]
+fn expect_reports_what_went_wrong() {
+    read("").expect("Card should be read");
+}
+
+#[test]
+fn simulating_leaves_the_machine_alone() {
+    let cr = CardReader::Locked(Locked {});
+    let (would_be, cmds) = cr
+        .simulate(CardReaderEvents::CardReadable("goodguy".to_string()))
+        .unwrap();
+    assert!(matches!(would_be, CardReader::ReadingCard(_)));
+    assert_eq!(
+        cmds,
+        vec![
+            Commands::ProcessData("goodguy".to_string()),
+            Commands::StartBlinkingLight
+        ]
+    );
+    assert!(matches!(cr, CardReader::Locked(_)));
+}
+
+#[test]
+fn transactions_apply_on_commit() {
+    let mut cr = CardReader::Locked(Locked {});
+    let transaction = cr
+        .try_event(CardReaderEvents::CardReadable("goodguy".to_string()))
+        .unwrap()
+        .and_event(CardReaderEvents::CardAccepted)
+        .unwrap();
+    assert!(matches!(transaction.state(), CardReader::DoorOpen(_)));
+    let cmds = transaction.commit();
+    assert_eq!(
+        cmds,
+        vec![
+            Commands::ProcessData("goodguy".to_string()),
+            Commands::StartBlinkingLight,
+            Commands::StopBlinkingLight
+        ]
+    );
+    assert!(matches!(cr, CardReader::DoorOpen(_)));
+}
+
+#[test]
+fn transactions_can_be_rolled_back() {
+    let mut cr = CardReader::Locked(Locked {});
+    let transaction = cr
+        .try_event(CardReaderEvents::CardReadable("goodguy".to_string()))
+        .unwrap();
+    assert_eq!(transaction.commands().len(), 2);
+    transaction.rollback();
+    assert!(matches!(cr, CardReader::Locked(_)));
+
+    // Failures along the way roll back too
+    let failed = cr
+        .try_event(CardReaderEvents::CardReadable("goodguy".to_string()))
+        .unwrap()
+        .and_event(CardReaderEvents::DoorClosed);
+    assert!(matches!(failed, Err(TransitionError::InvalidTransition(_))));
+    assert!(matches!(cr, CardReader::Locked(_)));
+}
+
+#[test]
+fn batches_are_all_or_nothing() {
+    let mut cr = CardReader::Locked(Locked {});
+    let err = cr
+        .try_events(vec![
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            CardReaderEvents::CardRejected,
+            CardReaderEvents::CardReadable(String::new()),
+        ])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        BatchError {
+            index: 2,
+            error: TransitionError::Handler(CardError)
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Event 2 of the batch failed: Transition failed: The card was blank"
+    );
+    assert!(matches!(cr, CardReader::Locked(_)));
+
+    let cmds = cr
+        .try_events(vec![
+            CardReaderEvents::CardReadable("goodguy".to_string()),
+            CardReaderEvents::CardAccepted,
+            CardReaderEvents::DoorClosed,
+        ])
+        .unwrap()
+        .commit();
+    assert_eq!(cmds.len(), 3);
+    assert!(matches!(cr, CardReader::Locked(_)));
+}